@@ -0,0 +1,199 @@
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+/// Register and delete the task with `schtasks.exe`.
+#[cfg(windows)]
+pub mod windows;
+
+/// Name of the scheduled task, at the root of the task library.
+pub const TASK_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Privileges the task runs with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunLevel {
+    /// elevated, without a UAC prompt.
+    Highest,
+    LeastPrivilege,
+}
+
+/// A Task Scheduler task, started when a user logs on.
+///
+/// Rendered as [Task Scheduler XML](https://learn.microsoft.com/en-us/windows/win32/taskschd/task-scheduler-schema):
+///
+/// ```rust
+/// use fitgirl_ecoqos::autostart::LogonTask;
+///
+/// let task = LogonTask::new(r"PC\alice", r"C:\Tools\fitgirl-ecoqos.exe").with_arguments(["run"]);
+/// let xml = task.to_xml();
+/// assert!(xml.contains(r"<UserId>PC\alice</UserId>"));
+/// assert!(xml.contains("<Arguments>run</Arguments>"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogonTask {
+    pub description: String,
+    /// `DOMAIN\user` whose logon starts the task, and who runs it.
+    pub user_id: String,
+    pub run_level: RunLevel,
+    pub command: PathBuf,
+    pub arguments: Vec<String>,
+}
+
+impl LogonTask {
+    /// run `command` elevated when `user_id` logs on.
+    pub fn new(user_id: impl Into<String>, command: impl Into<PathBuf>) -> Self {
+        Self {
+            description: env!("CARGO_PKG_DESCRIPTION").to_string(),
+            user_id: user_id.into(),
+            run_level: RunLevel::Highest,
+            command: command.into(),
+            arguments: vec![],
+        }
+    }
+
+    pub fn with_arguments(
+        mut self,
+        arguments: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.arguments = arguments.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_run_level(mut self, run_level: RunLevel) -> Self {
+        self.run_level = run_level;
+        self
+    }
+
+    /// the daemon, with `config` if given, as the task of `user_id`.
+    pub fn daemon(user_id: impl Into<String>, executable: &Path, config: Option<&Path>) -> Self {
+        let mut arguments = vec!["run".to_string()];
+        if let Some(config) = config {
+            arguments.push("--config".to_string());
+            arguments.push(config.to_string_lossy().into_owned());
+        }
+        Self::new(user_id, executable).with_arguments(arguments)
+    }
+
+    /// the task definition, to be saved as UTF-16, see [`LogonTask::to_utf16`].
+    ///
+    /// The task starts without a time limit, on battery too, and once at a time.
+    pub fn to_xml(&self) -> String {
+        let Self {
+            description,
+            user_id,
+            run_level,
+            command,
+            arguments,
+        } = self;
+        let description = escape(description);
+        let user_id = escape(user_id);
+        let run_level = match run_level {
+            RunLevel::Highest => "HighestAvailable",
+            RunLevel::LeastPrivilege => "LeastPrivilege",
+        };
+        let command = escape(&command.to_string_lossy());
+
+        let mut xml = format!(
+            r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Description>{description}</Description>
+    <URI>\{TASK_NAME}</URI>
+  </RegistrationInfo>
+  <Triggers>
+    <LogonTrigger>
+      <Enabled>true</Enabled>
+      <UserId>{user_id}</UserId>
+    </LogonTrigger>
+  </Triggers>
+  <Principals>
+    <Principal id="Author">
+      <UserId>{user_id}</UserId>
+      <LogonType>InteractiveToken</LogonType>
+      <RunLevel>{run_level}</RunLevel>
+    </Principal>
+  </Principals>
+  <Settings>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+    <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>
+    <Priority>7</Priority>
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>{command}</Command>
+"#
+        );
+        if !arguments.is_empty() {
+            let arguments: Vec<_> = arguments.iter().map(|arg| quote(arg)).collect();
+            let _ = writeln!(
+                xml,
+                "      <Arguments>{}</Arguments>",
+                escape(&arguments.join(" "))
+            );
+        }
+        xml.push_str(
+            "    </Exec>
+  </Actions>
+</Task>
+",
+        );
+        xml
+    }
+
+    /// [`LogonTask::to_xml`] as UTF-16 LE with a BOM, as `schtasks /XML` expects.
+    pub fn to_utf16(&self) -> Vec<u8> {
+        let xml = self.to_xml();
+        let units = std::iter::once(0xfeff).chain(xml.encode_utf16());
+        units.flat_map(u16::to_le_bytes).collect()
+    }
+}
+
+/// escape text and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// quote an argument the way `CommandLineToArgvW` splits it back.
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // escape the backslashes before a quote, and the quote
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    // backslashes before the closing quote
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,90 @@
+use std::{ffi::OsStr, fs, io, path::Path, process::Command};
+
+use tracing::info;
+use windows::{
+    Win32::System::RemoteDesktop::{
+        WTS_CURRENT_SERVER_HANDLE, WTS_CURRENT_SESSION, WTS_INFO_CLASS, WTSDomainName,
+        WTSFreeMemory, WTSQuerySessionInformationW, WTSUserName,
+    },
+    core::PWSTR,
+};
+
+use super::{LogonTask, TASK_NAME};
+use crate::Error;
+
+/// a string about the session of this process.
+fn session_info(class: WTS_INFO_CLASS) -> Result<String, Error> {
+    let mut buffer = PWSTR::null();
+    let mut len = 0;
+    unsafe {
+        WTSQuerySessionInformationW(
+            Some(WTS_CURRENT_SERVER_HANDLE),
+            WTS_CURRENT_SESSION,
+            class,
+            &mut buffer,
+            &mut len,
+        )?;
+        let value = buffer.to_string();
+        WTSFreeMemory(buffer.as_ptr().cast());
+        Ok(value.map_err(io::Error::other)?)
+    }
+}
+
+/// `DOMAIN\user` logged on to the session of this process.
+///
+/// Not the user of the process itself, which is the administrator when elevated
+/// with another account's credentials.
+fn current_user() -> Result<String, Error> {
+    let user = session_info(WTSUserName)?;
+    if user.is_empty() {
+        let e = io::Error::new(io::ErrorKind::NotFound, "no user logged on to this session");
+        return Err(e.into());
+    }
+    Ok(format!("{}\\{user}", session_info(WTSDomainName)?))
+}
+
+fn schtasks(args: &[&OsStr]) -> Result<(), Error> {
+    let output = Command::new("schtasks.exe").args(args).output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Schtasks(message.trim().to_string()));
+    }
+    Ok(())
+}
+
+/// register the task of the user logged on, replacing any previous one,
+/// from an elevated prompt.
+///
+/// The task runs `executable`, with `config` if given.
+pub fn install(executable: &Path, config: Option<&Path>) -> Result<(), Error> {
+    let config = config.map(std::path::absolute).transpose()?;
+    let task = LogonTask::daemon(current_user()?, executable, config.as_deref());
+
+    let path = std::env::temp_dir().join(format!("{TASK_NAME}-task.xml"));
+    fs::write(&path, task.to_utf16())?;
+    let result = schtasks(&[
+        "/Create".as_ref(),
+        "/TN".as_ref(),
+        TASK_NAME.as_ref(),
+        "/XML".as_ref(),
+        path.as_os_str(),
+        "/F".as_ref(),
+    ]);
+    let _ = fs::remove_file(&path);
+    result?;
+
+    info!("registered task {TASK_NAME}, started at next logon");
+    Ok(())
+}
+
+/// delete the task.
+pub fn uninstall() -> Result<(), Error> {
+    schtasks(&[
+        "/Delete".as_ref(),
+        "/TN".as_ref(),
+        TASK_NAME.as_ref(),
+        "/F".as_ref(),
+    ])?;
+    info!("deleted task {TASK_NAME}");
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use ahash::AHashMap;
+use futures_lite::{Stream, StreamExt, stream};
+use listen_new_proc::ProcessEvent;
+
+use super::{ProcessSource, QosController};
+use crate::Error;
+
+/// Replays scripted process events.
+#[derive(Debug, Default)]
+pub struct FakeSource {
+    running: Vec<ProcessEvent>,
+    events: Vec<ProcessEvent>,
+    keep_open: bool,
+}
+
+impl FakeSource {
+    pub fn new(events: impl IntoIterator<Item = ProcessEvent>) -> Self {
+        Self {
+            running: vec![],
+            events: events.into_iter().collect(),
+            keep_open: false,
+        }
+    }
+
+    /// processes already running, returned by [`ProcessSource::snapshot`].
+    pub fn with_running(mut self, running: impl IntoIterator<Item = ProcessEvent>) -> Self {
+        self.running = running.into_iter().collect();
+        self
+    }
+
+    /// wait forever after the scripted events, instead of ending, like real sources.
+    pub fn keep_open(mut self) -> Self {
+        self.keep_open = true;
+        self
+    }
+}
+
+impl ProcessSource for FakeSource {
+    type Events = Pin<Box<dyn Stream<Item = Result<ProcessEvent, Error>> + Send>>;
+
+    /// yields every scripted event once, then ends, unless kept open.
+    fn events(&mut self) -> Result<Self::Events, Error> {
+        let events = stream::iter(std::mem::take(&mut self.events)).map(Ok);
+        Ok(match self.keep_open {
+            true => Box::pin(events.chain(stream::pending())),
+            false => Box::pin(events),
+        })
+    }
+
+    fn snapshot(&mut self) -> Result<Vec<ProcessEvent>, Error> {
+        Ok(self.running.clone())
+    }
+}
+
+/// `(pid, enable)` passed to [`QosController::apply`].
+pub type Applied = (u32, Option<bool>);
+
+/// `(tid, name)` returned by [`QosController::threads`].
+type Threads = Vec<(u32, String)>;
+
+/// Records applied efficiency modes.
+///
+/// Clones share the same records, and threads.
+#[derive(Debug, Clone, Default)]
+pub struct FakeController {
+    applied: Arc<Mutex<Vec<Applied>>>,
+    /// `(tid, enable)` passed to [`QosController::apply_thread`].
+    applied_threads: Arc<Mutex<Vec<Applied>>>,
+    /// pid -> `(tid, name)` of its threads.
+    threads: Arc<Mutex<AHashMap<u32, Threads>>>,
+}
+
+impl FakeController {
+    /// threads of `pid` returned by [`QosController::threads`] from now on,
+    /// processes have none by default.
+    pub fn set_threads<'a>(&self, pid: u32, threads: impl IntoIterator<Item = (u32, &'a str)>) {
+        let threads = threads
+            .into_iter()
+            .map(|(tid, name)| (tid, name.to_string()))
+            .collect();
+        self.threads.lock().unwrap().insert(pid, threads);
+    }
+
+    /// every `(tid, enable)` applied to threads so far, in order.
+    pub fn applied_threads(&self) -> Vec<Applied> {
+        self.applied_threads.lock().unwrap().clone()
+    }
+
+    /// every `(pid, enable)` applied so far, in order.
+    pub fn applied(&self) -> Vec<Applied> {
+        self.applied.lock().unwrap().clone()
+    }
+
+    /// last applied state of each process.
+    pub fn states(&self) -> AHashMap<u32, Option<bool>> {
+        self.applied.lock().unwrap().iter().copied().collect()
+    }
+
+    /// last applied state of each thread.
+    pub fn thread_states(&self) -> AHashMap<u32, Option<bool>> {
+        self.applied_threads
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect()
+    }
+}
+
+impl QosController for FakeController {
+    /// the last applied state, `None` if never applied.
+    type Saved = Option<bool>;
+    /// the last applied state, `None` if never applied.
+    type SavedThread = Option<bool>;
+
+    fn apply(&self, pid: u32, enable: Option<bool>) -> Result<(), Error> {
+        self.applied.lock().unwrap().push((pid, enable));
+        Ok(())
+    }
+
+    fn query(&self, pid: u32) -> Result<bool, Error> {
+        Ok(self.states().get(&pid) == Some(&Some(true)))
+    }
+
+    fn save(&self, pid: u32) -> Result<Self::Saved, Error> {
+        Ok(self.states().get(&pid).copied().flatten())
+    }
+
+    /// recorded as an applied state.
+    fn restore(&self, pid: u32, saved: &Self::Saved) -> Result<(), Error> {
+        self.apply(pid, *saved)
+    }
+
+    fn threads(&self, pid: u32) -> Result<Vec<(u32, String)>, Error> {
+        let threads = self.threads.lock().unwrap();
+        Ok(threads.get(&pid).cloned().unwrap_or_default())
+    }
+
+    fn apply_thread(&self, _pid: u32, tid: u32, enable: Option<bool>) -> Result<(), Error> {
+        self.applied_threads.lock().unwrap().push((tid, enable));
+        Ok(())
+    }
+
+    fn save_thread(&self, _pid: u32, tid: u32) -> Result<Self::SavedThread, Error> {
+        Ok(self.thread_states().get(&tid).copied().flatten())
+    }
+
+    /// recorded as an applied state.
+    fn restore_thread(&self, pid: u32, tid: u32, saved: &Self::SavedThread) -> Result<(), Error> {
+        self.apply_thread(pid, tid, *saved)
+    }
+}
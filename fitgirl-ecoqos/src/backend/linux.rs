@@ -0,0 +1,431 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use futures_lite::{Stream, StreamExt};
+use listen_new_proc::{ProcessEvent, linux::procfs};
+
+use super::{ProcessSource, QosController};
+use crate::{
+    Error,
+    config::{CgroupConfig, LinuxConfig},
+};
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: u32 = 13;
+const IOPRIO_CLASS_NONE: u32 = 0;
+const IOPRIO_CLASS_IDLE: u32 = 3;
+
+/// Process creation and exit events from the proc connector, or `/proc` polling.
+#[derive(Debug, Default)]
+pub struct ProcSource;
+
+impl ProcessSource for ProcSource {
+    type Events = Pin<Box<dyn Stream<Item = Result<ProcessEvent, Error>> + Send>>;
+
+    fn events(&mut self) -> Result<Self::Events, Error> {
+        let stream = listen_new_proc::process_event_stream()?;
+        Ok(Box::pin(stream.map(|result| Ok(result?))))
+    }
+
+    fn snapshot(&mut self) -> Result<Vec<ProcessEvent>, Error> {
+        Ok(procfs::snapshot()?.into_values().collect())
+    }
+}
+
+/// Scheduler settings of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadState {
+    pub policy: libc::c_int,
+    /// static priority of real-time policies, `0` otherwise.
+    pub priority: libc::c_int,
+    pub nice: i32,
+    /// raw I/O priority, class and level.
+    pub ioprio: u32,
+}
+
+impl ThreadState {
+    fn query(tid: u32) -> io::Result<Self> {
+        Ok(Self {
+            policy: scheduler(tid)?,
+            priority: sched_priority(tid)?,
+            nice: nice(tid)?,
+            ioprio: ioprio(tid)?,
+        })
+    }
+
+    fn set(&self, tid: u32) -> io::Result<()> {
+        set_scheduler(tid, self.policy, self.priority)?;
+        set_nice(tid, self.nice)?;
+        set_ioprio(tid, self.ioprio)
+    }
+}
+
+/// Scheduler settings of every thread of a process, and its cgroup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedState {
+    /// `(tid, state)` of each thread.
+    pub threads: Vec<(u32, ThreadState)>,
+    /// relative to the cgroup root, `None` for the root itself.
+    pub cgroup: Option<PathBuf>,
+}
+
+/// Approximates efficiency mode with scheduler policy, nice value,
+/// I/O priority and cgroup limits, see [`LinuxConfig`].
+///
+/// All of them are per thread on Linux, so every thread of the process is updated.
+/// New threads inherit them from their creator.
+#[derive(Debug, Clone, Default)]
+pub struct LinuxController {
+    config: LinuxConfig,
+    /// where the cgroup v2 hierarchy holding the configured cgroup is mounted.
+    cgroup_root: Option<PathBuf>,
+    /// pid -> cgroup it was moved into ours from, relative to the root.
+    origins: Arc<Mutex<HashMap<u32, PathBuf>>>,
+}
+
+impl LinuxController {
+    /// create the cgroup, if any, and set its limits.
+    pub fn new(config: LinuxConfig) -> Result<Self, Error> {
+        let cgroup_root = match &config.cgroup {
+            Some(cgroup) => {
+                setup_cgroup(cgroup)?;
+                Some(cgroup_root(&cgroup.path)?)
+            }
+            None => None,
+        };
+        Ok(Self {
+            config,
+            cgroup_root,
+            origins: Arc::default(),
+        })
+    }
+
+    /// like [`LinuxController::new`], without creating the cgroup or writing its limits,
+    /// for read-only commands such as `status`.
+    pub fn query_only(config: LinuxConfig) -> Self {
+        let cgroup_root = config
+            .cgroup
+            .as_ref()
+            .and_then(|cgroup| cgroup_root(&cgroup.path).ok());
+        Self {
+            config,
+            cgroup_root,
+            origins: Arc::default(),
+        }
+    }
+
+    /// whether `pid` is in the configured cgroup, comparing full paths.
+    fn in_cgroup(&self, pid: u32) -> io::Result<bool> {
+        let (Some(CgroupConfig { path, .. }), Some(root)) =
+            (&self.config.cgroup, &self.cgroup_root)
+        else {
+            return Ok(false);
+        };
+        let cgroup = cgroup_of(pid)?.unwrap_or_default();
+        Ok(root.join(cgroup) == *path)
+    }
+
+    /// move `pid` into the configured cgroup, remembering where it was.
+    fn enter_cgroup(&self, pid: u32, path: &Path) -> io::Result<()> {
+        if self.in_cgroup(pid)? {
+            return Ok(());
+        }
+        let origin = cgroup_of(pid)?.unwrap_or_default();
+        move_to_cgroup(path, pid)?;
+
+        let mut origins = self.origins.lock().unwrap();
+        // forget processes which exited meanwhile, their PID may be reused
+        origins.retain(|pid, _| Path::new(&format!("/proc/{pid}")).exists());
+        origins.insert(pid, origin);
+        Ok(())
+    }
+
+    /// move `pid` out of the configured cgroup, back into `origin`, relative to the root.
+    ///
+    /// Defaults to where it was moved from by this controller, or to the root cgroup.
+    fn leave_cgroup(&self, pid: u32, origin: Option<&Path>) -> io::Result<()> {
+        let Some(root) = &self.cgroup_root else {
+            return Ok(());
+        };
+        let recorded = self.origins.lock().unwrap().remove(&pid);
+        if !self.in_cgroup(pid)? {
+            return Ok(());
+        }
+        let origin = origin.or(recorded.as_deref()).unwrap_or(Path::new(""));
+        move_to_cgroup(&root.join(origin), pid)
+    }
+
+    fn throttle(&self, tid: u32) -> io::Result<()> {
+        let LinuxConfig {
+            sched_idle,
+            nice,
+            ioprio_idle,
+            ..
+        } = self.config;
+
+        if sched_idle {
+            set_scheduler(tid, libc::SCHED_IDLE, 0)?;
+        }
+        set_nice(tid, nice)?;
+        if ioprio_idle {
+            set_ioprio_class(tid, IOPRIO_CLASS_IDLE)?;
+        }
+        Ok(())
+    }
+
+    fn unthrottle(&self, tid: u32) -> io::Result<()> {
+        set_scheduler(tid, libc::SCHED_OTHER, 0)?;
+        set_nice(tid, 0)?;
+        set_ioprio_class(tid, IOPRIO_CLASS_NONE)
+    }
+}
+
+/// ignore errors of threads which exited meanwhile.
+fn ignore_exited(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+        result => result,
+    }
+}
+
+impl QosController for LinuxController {
+    type Saved = SavedState;
+    type SavedThread = ThreadState;
+
+    /// `Some(true)` throttles, `Some(false)` and `None` both restore
+    /// the defaults, and move the process out of our cgroup, back where this
+    /// controller moved it from, or else into the root cgroup.
+    fn apply(&self, pid: u32, enable: Option<bool>) -> Result<(), Error> {
+        for tid in threads_of(pid)? {
+            ignore_exited(match enable {
+                Some(true) => self.throttle(tid),
+                _ => self.unthrottle(tid),
+            })?;
+        }
+
+        if let Some(CgroupConfig { path, .. }) = &self.config.cgroup {
+            match enable {
+                Some(true) => self.enter_cgroup(pid, path)?,
+                _ => self.leave_cgroup(pid, None)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// whether the process runs with `SCHED_IDLE`.
+    fn query(&self, pid: u32) -> Result<bool, Error> {
+        Ok(scheduler(pid)? == libc::SCHED_IDLE)
+    }
+
+    fn save(&self, pid: u32) -> Result<Self::Saved, Error> {
+        let mut threads = vec![];
+        for tid in threads_of(pid)? {
+            match ThreadState::query(tid) {
+                Ok(state) => threads.push((tid, state)),
+                Err(e) if e.raw_os_error() == Some(libc::ESRCH) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(SavedState {
+            threads,
+            cgroup: cgroup_of(pid)?,
+        })
+    }
+
+    /// threads created since [`QosController::save`] get the state of the main thread,
+    /// which they most likely inherited.
+    fn restore(&self, pid: u32, saved: &Self::Saved) -> Result<(), Error> {
+        let state_of = |tid| {
+            saved
+                .threads
+                .iter()
+                .find(|(saved, _)| *saved == tid)
+                .map(|(_, state)| state)
+        };
+        for tid in threads_of(pid)? {
+            match state_of(tid).or_else(|| state_of(pid)) {
+                Some(state) => ignore_exited(state.set(tid))?,
+                None => ignore_exited(self.unthrottle(tid))?,
+            }
+        }
+
+        self.leave_cgroup(pid, Some(saved.cgroup.as_deref().unwrap_or(Path::new(""))))?;
+
+        Ok(())
+    }
+
+    /// names from `/proc/<pid>/task/<tid>/comm`, threads which exited meanwhile are skipped.
+    fn threads(&self, pid: u32) -> Result<Vec<(u32, String)>, Error> {
+        let mut threads = vec![];
+        for tid in threads_of(pid)? {
+            match fs::read_to_string(format!("/proc/{pid}/task/{tid}/comm")) {
+                Ok(name) => threads.push((tid, name.trim_end_matches('\n').to_string())),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(threads)
+    }
+
+    /// the cgroup is left alone, it holds whole processes.
+    fn apply_thread(&self, _pid: u32, tid: u32, enable: Option<bool>) -> Result<(), Error> {
+        ignore_exited(match enable {
+            Some(true) => self.throttle(tid),
+            _ => self.unthrottle(tid),
+        })?;
+        Ok(())
+    }
+
+    fn save_thread(&self, _pid: u32, tid: u32) -> Result<Self::SavedThread, Error> {
+        Ok(ThreadState::query(tid)?)
+    }
+
+    fn restore_thread(&self, _pid: u32, tid: u32, saved: &Self::SavedThread) -> Result<(), Error> {
+        ignore_exited(saved.set(tid))?;
+        Ok(())
+    }
+}
+
+fn check(ret: libc::c_long) -> io::Result<libc::c_long> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// thread ids of a process, from `/proc/<pid>/task`.
+fn threads_of(pid: u32) -> io::Result<Vec<u32>> {
+    let mut tids = vec![];
+    for entry in fs::read_dir(format!("/proc/{pid}/task"))? {
+        if let Some(tid) = entry?.file_name().to_str().and_then(|s| s.parse().ok()) {
+            tids.push(tid);
+        }
+    }
+    Ok(tids)
+}
+
+/// scheduler policy of a thread, e.g. `SCHED_IDLE`.
+pub fn scheduler(tid: u32) -> io::Result<libc::c_int> {
+    let policy = unsafe { libc::sched_getscheduler(tid as libc::pid_t) };
+    check(policy.into())?;
+    Ok(policy)
+}
+
+/// static priority of a thread, for real-time policies.
+fn sched_priority(tid: u32) -> io::Result<libc::c_int> {
+    let mut param = libc::sched_param { sched_priority: 0 };
+    check(unsafe { libc::sched_getparam(tid as libc::pid_t, &mut param) }.into())?;
+    Ok(param.sched_priority)
+}
+
+fn set_scheduler(tid: u32, policy: libc::c_int, priority: libc::c_int) -> io::Result<()> {
+    let param = libc::sched_param {
+        sched_priority: priority,
+    };
+    check(unsafe { libc::sched_setscheduler(tid as libc::pid_t, policy, &param) }.into())?;
+    Ok(())
+}
+
+/// nice value of a thread.
+pub fn nice(tid: u32) -> io::Result<i32> {
+    // -1 is a valid nice value, clear errno to tell it from errors
+    unsafe { *libc::__errno_location() = 0 };
+    let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, tid as libc::id_t) };
+    let e = io::Error::last_os_error();
+    match e.raw_os_error() {
+        Some(0) => Ok(nice),
+        _ => Err(e),
+    }
+}
+
+fn set_nice(tid: u32, nice: i32) -> io::Result<()> {
+    check(unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) }.into())?;
+    Ok(())
+}
+
+/// I/O scheduling class of a thread, e.g. `3` for idle.
+pub fn ioprio_class(tid: u32) -> io::Result<u32> {
+    Ok(ioprio(tid)? >> IOPRIO_CLASS_SHIFT)
+}
+
+fn set_ioprio_class(tid: u32, class: u32) -> io::Result<()> {
+    set_ioprio(tid, class << IOPRIO_CLASS_SHIFT)
+}
+
+fn ioprio(tid: u32) -> io::Result<u32> {
+    let ioprio = check(unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, tid) })?;
+    Ok(ioprio as u32)
+}
+
+fn set_ioprio(tid: u32, ioprio: u32) -> io::Result<()> {
+    check(unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, ioprio) })?;
+    Ok(())
+}
+
+fn setup_cgroup(
+    CgroupConfig {
+        path,
+        cpu_weight,
+        cpu_max,
+    }: &CgroupConfig,
+) -> io::Result<()> {
+    // enable the cpu controller for children of the parent, may be enabled already
+    if let Some(parent) = path.parent() {
+        let _ = fs::write(parent.join("cgroup.subtree_control"), "+cpu");
+    }
+    fs::create_dir_all(path)?;
+
+    if let Some(weight) = cpu_weight {
+        fs::write(path.join("cpu.weight"), weight.to_string())?;
+    }
+    if let Some(max) = cpu_max {
+        fs::write(path.join("cpu.max"), max)?;
+    }
+    Ok(())
+}
+
+fn move_to_cgroup(path: &Path, pid: u32) -> io::Result<()> {
+    fs::write(path.join("cgroup.procs"), pid.to_string())
+}
+
+/// where the cgroup v2 hierarchy holding `path` is mounted, e.g. `/sys/fs/cgroup`,
+/// or `/sys/fs/cgroup/unified` on hybrid hierarchies.
+pub fn cgroup_root(path: &Path) -> io::Result<PathBuf> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo")?;
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            // mount point is the 5th field, the filesystem type follows the separator
+            let (mount, fs) = line.split_once(" - ")?;
+            fs.starts_with("cgroup2 ")
+                .then(|| mount.split(' ').nth(4))
+                .flatten()
+        })
+        .map(Path::new)
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .map(Path::to_path_buf)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not in a cgroup v2 hierarchy", path.display()),
+            )
+        })
+}
+
+/// cgroup v2 of a process, relative to the cgroup root, e.g. `user.slice/foo`.
+pub fn cgroup_of(pid: u32) -> io::Result<Option<PathBuf>> {
+    let cgroup = fs::read_to_string(format!("/proc/{pid}/cgroup"))?;
+    Ok(cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::/"))
+        .filter(|cgroup| !cgroup.is_empty())
+        .map(PathBuf::from))
+}
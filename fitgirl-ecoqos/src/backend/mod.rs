@@ -0,0 +1,60 @@
+use std::fmt::Debug;
+
+use futures_lite::Stream;
+use listen_new_proc::ProcessEvent;
+
+use crate::Error;
+
+/// In-memory backend, for tests and dry runs.
+pub mod fake;
+/// Proc connector and scheduler backend.
+#[cfg(target_os = "linux")]
+pub mod linux;
+/// WMI and Win32 backend.
+#[cfg(windows)]
+pub mod windows;
+
+/// Source of process events.
+pub trait ProcessSource {
+    type Events: Stream<Item = Result<ProcessEvent, Error>> + Unpin;
+
+    /// start listening for process events.
+    fn events(&mut self) -> Result<Self::Events, Error>;
+
+    /// processes running right now, as creation events.
+    fn snapshot(&mut self) -> Result<Vec<ProcessEvent>, Error>;
+}
+
+/// Applies and queries QoS of processes.
+pub trait QosController {
+    /// State of a process before an action, see [`QosController::save`].
+    type Saved: Debug + Clone;
+    /// State of a thread before an action, see [`QosController::save_thread`].
+    type SavedThread: Debug + Clone;
+
+    /// toggle efficiency mode, with `toggle_efficiency_mode` semantics:
+    ///
+    /// `Some(true)` for EcoQoS, `Some(false)` for HighQoS, `None` to let system decide.
+    fn apply(&self, pid: u32, enable: Option<bool>) -> Result<(), Error>;
+
+    /// check whether EcoQoS is enabled on a process.
+    fn query(&self, pid: u32) -> Result<bool, Error>;
+
+    /// query what [`QosController::apply`] is about to change.
+    fn save(&self, pid: u32) -> Result<Self::Saved, Error>;
+
+    /// put back a state returned by [`QosController::save`].
+    fn restore(&self, pid: u32, saved: &Self::Saved) -> Result<(), Error>;
+
+    /// `(tid, name)` of every thread of a process, unnamed threads have an empty name.
+    fn threads(&self, pid: u32) -> Result<Vec<(u32, String)>, Error>;
+
+    /// like [`QosController::apply`], on a single thread `tid` of `pid`.
+    fn apply_thread(&self, pid: u32, tid: u32, enable: Option<bool>) -> Result<(), Error>;
+
+    /// query what [`QosController::apply_thread`] is about to change.
+    fn save_thread(&self, pid: u32, tid: u32) -> Result<Self::SavedThread, Error>;
+
+    /// put back a state returned by [`QosController::save_thread`].
+    fn restore_thread(&self, pid: u32, tid: u32, saved: &Self::SavedThread) -> Result<(), Error>;
+}
@@ -0,0 +1,173 @@
+use std::{ffi::OsString, os::windows::ffi::OsStringExt, path::PathBuf, pin::Pin};
+
+use futures_lite::{Stream, StreamExt};
+use listen_new_proc::ProcessEvent;
+use win32_ecoqos::{
+    process::{
+        PriorityClass, ProcessQos, ThrottlingState, ecoqos_enabled, priority_class,
+        throttling_state,
+    },
+    thread,
+    utils::{Processes, Threads},
+    windows::{
+        Win32::{
+            Foundation::{CloseHandle, HANDLE},
+            System::Threading::{
+                OpenProcess, PROCESS_NAME_WIN32, PROCESS_QUERY_INFORMATION,
+                PROCESS_QUERY_LIMITED_INFORMATION, QueryFullProcessImageNameW, THREAD_PRIORITY,
+            },
+        },
+        core::PWSTR,
+    },
+};
+
+use super::{ProcessSource, QosController};
+use crate::{Error, config::WindowsConfig};
+
+/// Process creation and exit events from WMI.
+#[derive(Debug, Default)]
+pub struct WmiSource;
+
+impl ProcessSource for WmiSource {
+    type Events = Pin<Box<dyn Stream<Item = Result<ProcessEvent, Error>>>>;
+
+    fn events(&mut self) -> Result<Self::Events, Error> {
+        let stream = listen_new_proc::process_event_stream()?;
+        Ok(Box::pin(stream.map(|result| Ok(result?))))
+    }
+
+    fn snapshot(&mut self) -> Result<Vec<ProcessEvent>, Error> {
+        Ok(Processes::try_new()?
+            .map(|p| {
+                let name = p.process_name.to_string_lossy();
+                let mut event = ProcessEvent::created(p.process_id, p.process_parent_id, name);
+                // command lines are not available without reading the PEB
+                event.executable_path = executable_path(p.process_id);
+                event
+            })
+            .collect())
+    }
+}
+
+/// full image path of a process, `None` if access is denied.
+fn executable_path(pid: u32) -> Option<PathBuf> {
+    let hprocess = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }.ok()?;
+
+    let mut buffer = [0u16; 1024];
+    let mut len = buffer.len() as u32;
+    let result = unsafe {
+        QueryFullProcessImageNameW(
+            hprocess,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &mut len,
+        )
+    };
+    let _ = unsafe { CloseHandle(hprocess) };
+
+    result.ok()?;
+    Some(OsString::from_wide(&buffer[..len as usize]).into())
+}
+
+/// Throttling state and priority class of a process, before efficiency mode was toggled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavedQos {
+    pub throttling: ThrottlingState,
+    pub priority: PriorityClass,
+}
+
+/// Throttling state and priority of a thread, before efficiency mode was toggled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavedThreadQos {
+    pub throttling: ThrottlingState,
+    pub priority: THREAD_PRIORITY,
+}
+
+/// Efficiency mode toggles from `win32_ecoqos`, see [`WindowsConfig`].
+#[derive(Debug, Clone, Default)]
+pub struct Win32Controller {
+    config: WindowsConfig,
+}
+
+impl Win32Controller {
+    pub fn new(config: WindowsConfig) -> Self {
+        Self { config }
+    }
+
+    fn with_handle<T>(
+        pid: u32,
+        f: impl FnOnce(HANDLE) -> Result<T, win32_ecoqos::windows_result::Error>,
+    ) -> Result<T, Error> {
+        let hprocess = unsafe { OpenProcess(PROCESS_QUERY_INFORMATION, false, pid)? };
+        let result = f(hprocess);
+        let _ = unsafe { CloseHandle(hprocess) };
+
+        Ok(result?)
+    }
+}
+
+impl QosController for Win32Controller {
+    type Saved = SavedQos;
+    type SavedThread = SavedThreadQos;
+
+    /// like `toggle_efficiency_mode`, throttled processes also ignore timer
+    /// resolution requests if configured. Other actions leave them to the system.
+    fn apply(&self, pid: u32, enable: Option<bool>) -> Result<(), Error> {
+        let qos = ProcessQos::efficiency_mode(enable);
+        let qos = match enable {
+            Some(true) if self.config.ignore_timer_resolution => {
+                qos.ignore_timer_resolution(Some(true))
+            }
+            _ => qos,
+        };
+        Ok(qos.apply(pid)?)
+    }
+
+    fn query(&self, pid: u32) -> Result<bool, Error> {
+        Self::with_handle(pid, |hprocess| unsafe { ecoqos_enabled(hprocess) })
+    }
+
+    fn save(&self, pid: u32) -> Result<Self::Saved, Error> {
+        Self::with_handle(pid, |hprocess| unsafe {
+            Ok(SavedQos {
+                throttling: throttling_state(hprocess)?,
+                priority: priority_class(hprocess)?,
+            })
+        })
+    }
+
+    /// bits which were left to the system are left to it again,
+    /// rather than forced off.
+    fn restore(&self, pid: u32, saved: &Self::Saved) -> Result<(), Error> {
+        Ok(ProcessQos::new()
+            .throttling(saved.throttling)
+            .priority(saved.priority)
+            .apply(pid)?)
+    }
+
+    /// names of threads which can't be queried are empty.
+    fn threads(&self, pid: u32) -> Result<Vec<(u32, String)>, Error> {
+        Ok(Threads::for_process(pid)?
+            .of_process()
+            .map(|t| {
+                let name = t.get_name().unwrap_or_default();
+                (t.thread_id, name.to_string_lossy().into_owned())
+            })
+            .collect())
+    }
+
+    fn apply_thread(&self, _pid: u32, tid: u32, enable: Option<bool>) -> Result<(), Error> {
+        Ok(thread::toggle_efficiency_mode(tid, enable)?)
+    }
+
+    fn save_thread(&self, _pid: u32, tid: u32) -> Result<Self::SavedThread, Error> {
+        Ok(SavedThreadQos {
+            throttling: thread::query_qos(tid)?,
+            priority: thread::query_priority(tid)?,
+        })
+    }
+
+    fn restore_thread(&self, _pid: u32, tid: u32, saved: &Self::SavedThread) -> Result<(), Error> {
+        Ok(thread::set_qos(tid, saved.throttling, saved.priority)?)
+    }
+}
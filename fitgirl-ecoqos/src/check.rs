@@ -0,0 +1,401 @@
+use std::{fmt, ops::Range};
+
+use ahash::AHashMap;
+use serde::{Deserialize, de::IntoDeserializer};
+use toml_edit::{ImDocument, InlineTable, Item, TableLike, Value};
+
+use crate::{
+    config::{Config, Rule},
+    matcher::{self, Normalizer},
+};
+
+/// How bad a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// 1-based line and column, in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    /// locate a byte offset of `source`.
+    pub fn new(source: &str, offset: usize) -> Self {
+        let before = &source[..offset.min(source.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// A problem found by [`check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub location: Option<Location>,
+    /// 0-based index of the `[[rule]]` table.
+    pub rule: Option<usize>,
+    pub message: String,
+}
+
+/// `line:column: error: rule #0: message`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(Location { line, column }) = self.location {
+            write!(f, "{line}:{column}: ")?;
+        }
+        match self.severity {
+            Severity::Warning => write!(f, "warning: ")?,
+            Severity::Error => write!(f, "error: ")?,
+        }
+        if let Some(rule) = self.rule {
+            write!(f, "rule #{rule}: ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+const TOP_LEVEL_KEYS: &[&str] = &[
+    "blacklist",
+    "blacklist_regex",
+    "rule",
+    "case_sensitive",
+    "ignore_exe_suffix",
+    "inherit_depth",
+    "linux",
+    "windows",
+];
+const RULE_KEYS: &[&str] = &[
+    "names",
+    "regex",
+    "paths",
+    "command_line_regex",
+    "parents",
+    "action",
+    "inherit_depth",
+    "threads",
+];
+const LINUX_KEYS: &[&str] = &["sched_idle", "nice", "ioprio_idle", "cgroup"];
+const CGROUP_KEYS: &[&str] = &["path", "cpu_weight", "cpu_max"];
+const WINDOWS_KEYS: &[&str] = &["ignore_timer_resolution"];
+/// keys only understood with the `regex` feature.
+const REGEX_KEYS: &[&str] = &["blacklist_regex", "regex", "command_line_regex"];
+
+/// Check the contents of a config file, problems are sorted by location.
+///
+/// Besides parse and type errors, every regex and glob is compiled on its own,
+/// unknown keys are warned about, as well as names listed more than once,
+/// or differing only in case when `case_sensitive` is set.
+pub fn check(source: &str) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        source,
+        normalizer: Normalizer::default(),
+        diagnostics: vec![],
+    };
+    checker.check();
+
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.sort_by_key(|d| d.location.map(|l| (l.line, l.column)));
+    diagnostics
+}
+
+/// Whether any diagnostic is an error.
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+struct Checker<'a> {
+    source: &'a str,
+    /// how the daemon compares names, from the options of the file.
+    normalizer: Normalizer,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn report(
+        &mut self,
+        severity: Severity,
+        span: Option<Range<usize>>,
+        rule: Option<usize>,
+        message: impl Into<String>,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            location: span.map(|span| {
+                // spans of values may start with the whitespace before them
+                let value = &self.source[span.clone()];
+                let start = span.start + (value.len() - value.trim_start().len());
+                Location::new(self.source, start)
+            }),
+            rule,
+            message: message.into(),
+        });
+    }
+
+    fn check(&mut self) {
+        let document = match ImDocument::parse(self.source) {
+            Ok(document) => document,
+            Err(e) => {
+                self.report(Severity::Error, e.span(), None, e.message().trim_end());
+                return;
+            }
+        };
+
+        let root = document.as_table();
+        self.types(root);
+
+        let option = |key| root.get(key).and_then(Item::as_bool).unwrap_or(false);
+        self.normalizer = Normalizer {
+            case_sensitive: option("case_sensitive"),
+            ignore_exe_suffix: option("ignore_exe_suffix"),
+        };
+        self.unknown_keys(root, TOP_LEVEL_KEYS, None, "");
+        if let Some(linux) = root.get("linux").and_then(Item::as_table_like) {
+            self.unknown_keys(linux, LINUX_KEYS, None, "linux.");
+            if let Some(cgroup) = linux.get("cgroup").and_then(Item::as_table_like) {
+                self.unknown_keys(cgroup, CGROUP_KEYS, None, "linux.cgroup.");
+            }
+        }
+        if let Some(windows) = root.get("windows").and_then(Item::as_table_like) {
+            self.unknown_keys(windows, WINDOWS_KEYS, None, "windows.");
+        }
+
+        let mut names = Names::default();
+        let rules = root.get("rule").map(tables).unwrap_or_default();
+        for (index, (rule, span)) in rules.into_iter().enumerate() {
+            self.unknown_keys(rule, RULE_KEYS, Some(index), "");
+            self.patterns(rule, Some(index));
+
+            let has = |keys: &[&str]| keys.iter().any(|key| rule.contains_key(key));
+            let by_name = has(&["names", "regex"]);
+            let filtered = has(&["paths", "command_line_regex", "parents"]);
+            if !by_name && !filtered {
+                let message = "no condition, matches nothing";
+                self.report(Severity::Warning, span, Some(index), message);
+            }
+            for (name, span) in strings(rule.get("names")) {
+                names.add(self, name, span, Some(index), !filtered);
+            }
+        }
+
+        self.patterns(root, None);
+        for (name, span) in strings(root.get("blacklist")) {
+            names.add(self, name, span, None, true);
+        }
+    }
+
+    /// types and values, as the daemon would load them.
+    ///
+    /// Every rule and top-level key is loaded on its own, so each of their errors
+    /// is reported, not only the first one.
+    fn types(&mut self, root: &toml_edit::Table) {
+        for (key, item) in root.iter() {
+            let fallback = root.key(key).and_then(|key| key.span());
+            let rules = match item {
+                Item::ArrayOfTables(_) | Item::Value(Value::Array(_)) if key == "rule" => {
+                    item.clone().into_value().ok()
+                }
+                _ => None,
+            };
+
+            if let Some(Value::Array(rules)) = rules {
+                for (index, rule) in rules.into_iter().enumerate() {
+                    let span = rule.span();
+                    if let Err(e) = Rule::deserialize(rule.into_deserializer()) {
+                        let span = e.span().or(span);
+                        self.report(Severity::Error, span, Some(index), e.message().trim_end());
+                    }
+                }
+            } else if let Ok(value) = item.clone().into_value() {
+                let mut table = InlineTable::new();
+                table.insert(key, value);
+                if let Err(e) = Config::deserialize(Value::InlineTable(table).into_deserializer()) {
+                    let span = e.span().or(fallback);
+                    self.report(Severity::Error, span, None, e.message().trim_end());
+                }
+            }
+        }
+    }
+
+    fn unknown_keys(
+        &mut self,
+        table: &dyn TableLike,
+        known: &[&str],
+        rule: Option<usize>,
+        prefix: &str,
+    ) {
+        for (key, _) in table.iter() {
+            let span = table.key(key).and_then(|key| key.span());
+            if !known.contains(&key) {
+                self.report(
+                    Severity::Warning,
+                    span,
+                    rule,
+                    format!("unknown key `{prefix}{key}`, ignored"),
+                );
+            } else if !cfg!(feature = "regex") && REGEX_KEYS.contains(&key) {
+                self.report(
+                    Severity::Warning,
+                    span,
+                    rule,
+                    format!("`{prefix}{key}` requires the `regex` feature, ignored"),
+                );
+            }
+        }
+    }
+
+    /// compile every regex and glob of a rule, or of the blacklists.
+    fn patterns(&mut self, table: &dyn TableLike, rule: Option<usize>) {
+        let (names, globs, regexes): (&[_], &[_], &[_]) = match rule {
+            Some(_) => (
+                &["names", "parents"],
+                &["paths"],
+                &["regex", "command_line_regex"],
+            ),
+            None => (&["blacklist"], &[], &["blacklist_regex"]),
+        };
+
+        for key in names {
+            for (pattern, span) in strings(table.get(key)) {
+                if matcher::is_glob(pattern)
+                    && let Err(e) = matcher::name_glob(pattern, self.normalizer)
+                {
+                    let message = format!("in `{key}`: {e}");
+                    self.report(Severity::Error, span, rule, message);
+                }
+            }
+        }
+
+        for key in globs {
+            for (pattern, span) in strings(table.get(key)) {
+                if let Err(e) = matcher::glob(pattern) {
+                    let message = format!("in `{key}`: {e}");
+                    self.report(Severity::Error, span, rule, message);
+                }
+            }
+        }
+
+        if rule.is_some() {
+            for (pattern, span) in strings(table.get("threads")) {
+                if let Err(e) = matcher::thread_glob(pattern, self.normalizer.case_sensitive) {
+                    let message = format!("in `threads`: {e}");
+                    self.report(Severity::Error, span, rule, message);
+                }
+            }
+        }
+
+        #[cfg(feature = "regex")]
+        for key in regexes {
+            for (pattern, span) in strings(table.get(key)) {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    // the message spans lines, to point at the error inside the pattern
+                    let message = format!("in `{key}`: invalid regex:\n{e}");
+                    self.report(Severity::Error, span, rule, message);
+                }
+            }
+        }
+        #[cfg(not(feature = "regex"))]
+        let _ = regexes;
+    }
+}
+
+/// names seen so far, to find duplicates.
+#[derive(Default)]
+struct Names<'a> {
+    /// lowercase normalized name -> first spelling
+    seen: AHashMap<String, Seen<'a>>,
+}
+
+#[derive(Clone, Copy)]
+struct Seen<'a> {
+    name: &'a str,
+    /// rule index, `None` for the blacklist.
+    rule: Option<usize>,
+    /// whether the rule matches on names alone, shadowing later rules.
+    unconditional: bool,
+}
+
+impl<'a> Names<'a> {
+    fn add(
+        &mut self,
+        checker: &mut Checker,
+        name: &'a str,
+        span: Option<Range<usize>>,
+        rule: Option<usize>,
+        unconditional: bool,
+    ) {
+        let current = Seen {
+            name,
+            rule,
+            unconditional,
+        };
+        let normalizer = checker.normalizer;
+        let key = normalizer.normalize(name).to_lowercase();
+        let Some(&first) = self.seen.get(&key) else {
+            self.seen.insert(key, current);
+            return;
+        };
+
+        let place = match first.rule {
+            Some(index) => format!("rule #{index}"),
+            None => "blacklist".to_string(),
+        };
+        let same = normalizer.normalize(first.name) == normalizer.normalize(name);
+        // other spellings the daemon treats as the same name
+        let quoted = match first.name == name {
+            true => format!("`{name}`"),
+            false => format!("`{name}`, same as `{}`,", first.name),
+        };
+        let message = if !same {
+            format!(
+                "`{name}` differs from `{}` in {place} only by case, names are case-sensitive",
+                first.name
+            )
+        } else if first.rule == rule {
+            format!("{quoted} is listed twice")
+        } else if first.unconditional {
+            format!("{quoted} is already matched by {place}, it never matches here")
+        } else {
+            // the first one only matches under conditions, this one may still be reached
+            if unconditional {
+                self.seen.insert(key, current);
+            }
+            return;
+        };
+        checker.report(Severity::Warning, span, rule, message);
+    }
+}
+
+/// tables of an array of tables, or of an array of inline tables, with their spans.
+fn tables(item: &Item) -> Vec<(&dyn TableLike, Option<Range<usize>>)> {
+    match item {
+        Item::ArrayOfTables(array) => array
+            .iter()
+            .map(|t| (t as &dyn TableLike, t.span()))
+            .collect(),
+        Item::Value(Value::Array(array)) => array
+            .iter()
+            .filter_map(Value::as_inline_table)
+            .map(|t| (t as &dyn TableLike, t.span()))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// strings of an array, with their spans, other values are left to serde.
+fn strings(item: Option<&Item>) -> Vec<(&str, Option<Range<usize>>)> {
+    let Some(array) = item.and_then(Item::as_array) else {
+        return vec![];
+    };
+    array
+        .iter()
+        .filter_map(|value| Some((value.as_str()?, value.span())))
+        .collect()
+}
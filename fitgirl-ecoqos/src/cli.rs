@@ -0,0 +1,290 @@
+use std::{convert::Infallible, fmt, io::Write, path::PathBuf, str::FromStr};
+
+use ahash::AHashMap;
+use clap::{Parser, Subcommand};
+use listen_new_proc::ProcessEvent;
+
+use crate::{
+    Error,
+    backend::{ProcessSource, QosController},
+    config::{Action, Config},
+    loader::Loader,
+    matcher::{Matcher, Normalizer, RuleMatch, Target},
+};
+
+/// Automatically throttle EcoQoS, by process name blacklist.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Config file to merge over the system-wide and per-user ones.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// `run` if omitted.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Apply rules to running and new processes, until stopped.
+    Run,
+    /// Apply an action to processes right now.
+    Apply {
+        /// PID, or process name.
+        target: ProcessTarget,
+        #[arg(long, value_enum, default_value_t = Action::Eco)]
+        action: Action,
+    },
+    /// Let the system decide QoS of processes again.
+    Restore {
+        /// PID, or process name.
+        target: ProcessTarget,
+    },
+    /// Show whether processes are throttled, the ones `list` shows by default.
+    Status {
+        /// PID, or process name.
+        target: Option<ProcessTarget>,
+    },
+    /// List running processes the rules apply to.
+    List,
+    /// Report problems of the config file, exit with 1 on errors.
+    CheckConfig,
+    /// Print the default config, with comments.
+    PrintDefaultConfig,
+    /// Write the default config to `--config` or the per-user file, unless it exists.
+    Init,
+    /// Print the effective config, merged from every source.
+    PrintConfig {
+        /// Print where each value comes from instead.
+        #[arg(long)]
+        origins: bool,
+    },
+    /// Install and start the Windows service, running at boot, with `--config` if given.
+    #[cfg(windows)]
+    InstallService,
+    /// Stop and uninstall the Windows service.
+    #[cfg(windows)]
+    UninstallService,
+    /// Start the daemon elevated at logon, with a scheduled task, with `--config` if given.
+    #[cfg(windows)]
+    InstallAutostart,
+    /// Delete the logon task.
+    #[cfg(windows)]
+    UninstallAutostart,
+    /// Entry point of the Windows service, not for the command line.
+    #[cfg(windows)]
+    #[command(hide = true)]
+    Service,
+}
+
+impl Cli {
+    /// the subcommand, `run` if omitted.
+    pub fn command(&self) -> Command {
+        self.command.clone().unwrap_or(Command::Run)
+    }
+
+    /// `--config`, or the per-user file.
+    pub fn config_path(&self) -> Result<PathBuf, Error> {
+        match &self.config {
+            Some(path) => Ok(path.clone()),
+            None => Config::default_path(),
+        }
+    }
+
+    /// system-wide and per-user files, then `--config`, then `FITGIRL_ECOQOS_*` variables.
+    pub fn loader(&self) -> Result<Loader, Error> {
+        Ok(Loader::standard(self.config.clone())?.with_env(std::env::vars()))
+    }
+}
+
+/// A process ID, or every process with this name, compared as rules compare names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessTarget {
+    Pid(u32),
+    Name(String),
+}
+
+impl FromStr for ProcessTarget {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse() {
+            Ok(pid) => Self::Pid(pid),
+            Err(_) => Self::Name(s.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for ProcessTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pid(pid) => write!(f, "PID {pid}"),
+            Self::Name(name) => write!(f, "{name:?}"),
+        }
+    }
+}
+
+impl ProcessTarget {
+    pub fn matches(&self, process: &ProcessEvent, normalizer: Normalizer) -> bool {
+        match self {
+            Self::Pid(pid) => process.process_id == *pid,
+            Self::Name(name) => normalizer.normalize(&process.name) == normalizer.normalize(name),
+        }
+    }
+}
+
+/// running processes matching `target`, fails if there is none.
+fn select(
+    source: &mut impl ProcessSource,
+    normalizer: Normalizer,
+    target: &ProcessTarget,
+) -> Result<Vec<ProcessEvent>, Error> {
+    let processes: Vec<_> = source
+        .snapshot()?
+        .into_iter()
+        .filter(|process| target.matches(process, normalizer))
+        .collect();
+    if processes.is_empty() {
+        return Err(Error::NoProcess(target.to_string()));
+    }
+    Ok(processes)
+}
+
+/// `apply` and `restore`: apply `action` to every process matching `target`.
+///
+/// Every process is tried, the first failure is returned.
+pub fn apply(
+    out: &mut impl Write,
+    source: &mut impl ProcessSource,
+    controller: &impl QosController,
+    normalizer: Normalizer,
+    target: &ProcessTarget,
+    action: Action,
+) -> Result<(), Error> {
+    let Some(enable) = action.efficiency_mode() else {
+        return Ok(());
+    };
+
+    let mut result = Ok(());
+    for ProcessEvent {
+        process_id, name, ..
+    } in select(source, normalizer, target)?
+    {
+        match controller.apply(process_id, enable) {
+            Ok(()) => writeln!(out, "{process_id:>7}  {name}: {action}")?,
+            Err(e) => {
+                writeln!(out, "{process_id:>7}  {name}: failed, {e}")?;
+                result = result.and(Err(e));
+            }
+        }
+    }
+    result
+}
+
+/// processes the daemon would apply an action to at startup, with the action, by PID.
+fn planned(
+    source: &mut impl ProcessSource,
+    matcher: &Matcher,
+) -> Result<Vec<(ProcessEvent, Action)>, Error> {
+    let processes = source.snapshot()?;
+    let names: AHashMap<_, _> = processes
+        .iter()
+        .map(|process| (process.process_id, process.name.as_str()))
+        .collect();
+
+    // like the startup sweep, processes are matched again once their parent is,
+    // to inherit its action
+    let mut matched = AHashMap::<u32, RuleMatch>::new();
+    let mut pending: Vec<_> = processes.iter().collect();
+    loop {
+        let before = pending.len();
+        pending.retain(|process| {
+            let mut target = Target::from_event(process);
+            target.parent_name = names.get(&process.parent_process_id).copied();
+            let rule = matcher.find_rule(&target).or_else(|| {
+                let parent = matched.get(&process.parent_process_id)?;
+                Some(RuleMatch {
+                    action: parent.action,
+                    inherit_depth: parent.inherit_depth.checked_sub(1)?,
+                    threads: None,
+                })
+            });
+            match rule {
+                // ignored processes are not tracked, their children inherit nothing
+                Some(rule) if rule.action.efficiency_mode().is_none() => false,
+                Some(rule) => {
+                    matched.insert(process.process_id, rule);
+                    false
+                }
+                None => true,
+            }
+        });
+        if pending.len() == before {
+            break;
+        }
+    }
+
+    let mut planned: Vec<_> = processes
+        .into_iter()
+        .filter_map(|process| {
+            let action = matched.get(&process.process_id)?.action;
+            Some((process, action))
+        })
+        .collect();
+    planned.sort_by_key(|(process, _)| process.process_id);
+    Ok(planned)
+}
+
+/// `list`: running processes the rules apply to.
+pub fn list(
+    out: &mut impl Write,
+    source: &mut impl ProcessSource,
+    matcher: &Matcher,
+) -> Result<(), Error> {
+    writeln!(out, "{:>7}  {:>7}  {:<8} NAME", "PID", "PPID", "ACTION")?;
+    for (process, action) in planned(source, matcher)? {
+        let ProcessEvent {
+            process_id,
+            parent_process_id,
+            name,
+            ..
+        } = process;
+        writeln!(
+            out,
+            "{process_id:>7}  {parent_process_id:>7}  {action:<8} {name}"
+        )?;
+    }
+    Ok(())
+}
+
+/// `status`: whether processes are throttled right now.
+pub fn status(
+    out: &mut impl Write,
+    source: &mut impl ProcessSource,
+    controller: &impl QosController,
+    matcher: &Matcher,
+    target: Option<&ProcessTarget>,
+) -> Result<(), Error> {
+    let processes: Vec<_> = match target {
+        Some(target) => select(source, matcher.normalizer(), target)?,
+        None => planned(source, matcher)?
+            .into_iter()
+            .map(|(process, _)| process)
+            .collect(),
+    };
+
+    writeln!(out, "{:>7}  {:<9} NAME", "PID", "THROTTLED")?;
+    for ProcessEvent {
+        process_id, name, ..
+    } in processes
+    {
+        let throttled = match controller.query(process_id) {
+            Ok(true) => "yes",
+            Ok(false) => "no",
+            // e.g. access denied
+            Err(_) => "unknown",
+        };
+        writeln!(out, "{process_id:>7}  {throttled:<9} {name}")?;
+    }
+    Ok(())
+}
@@ -1,20 +1,214 @@
-use std::fs;
+use std::{
+    fmt::{self, Write as _},
+    fs,
+    io::{ErrorKind, Write as _},
+    path::{Path, PathBuf},
+};
 
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
-use tracing::warn;
 
 use crate::Error;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
+    /// Processes to throttle, by name or name glob, e.g. `cls-magic2*_x??.exe`.
     pub blacklist: Vec<String>,
+    /// Processes to throttle, by regex.
     #[cfg(feature = "regex")]
     pub blacklist_regex: Vec<String>,
+    /// `[[rule]]` tables, checked in order before the blacklists.
+    #[serde(rename = "rule")]
+    pub rules: Vec<Rule>,
+    /// Compare names as-is, instead of ignoring case.
+    pub case_sensitive: bool,
+    /// Match `setup` with `setup.exe` and the other way around.
+    pub ignore_exe_suffix: bool,
+    /// Generations of descendants of a matched process which are matched too,
+    /// `0` to disable. Rules may override it.
+    pub inherit_depth: u32,
+    /// How EcoQoS is approximated on Linux, ignored elsewhere.
+    pub linux: LinuxConfig,
+    /// How EcoQoS is applied on Windows, ignored elsewhere.
+    pub windows: WindowsConfig,
+}
+
+/// The `[windows]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowsConfig {
+    /// Also ignore timer resolution requests of throttled processes,
+    /// so they can't keep the system on high-resolution timers. Windows 11 or newer.
+    pub ignore_timer_resolution: bool,
+}
+
+/// The `[linux]` table.
+///
+/// There is no EcoQoS on Linux, throttled processes are scheduled with
+/// `SCHED_IDLE`, a high nice value and the idle I/O class instead,
+/// optionally inside a cgroup with CPU limits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinuxConfig {
+    /// Use the `SCHED_IDLE` policy.
+    pub sched_idle: bool,
+    /// Nice value, from -20 to 19.
+    pub nice: i32,
+    /// Use the idle I/O scheduling class.
+    pub ioprio_idle: bool,
+    /// Move throttled processes into a cgroup v2.
+    pub cgroup: Option<CgroupConfig>,
+}
+
+/// The `[linux.cgroup]` table.
+///
+/// ```toml
+/// [linux.cgroup]
+/// path = "/sys/fs/cgroup/fitgirl-ecoqos"
+/// cpu_weight = 10
+/// cpu_max = "200000 100000"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CgroupConfig {
+    /// cgroup directory, created if missing.
+    pub path: PathBuf,
+    /// `cpu.weight`, from 1 to 10000.
+    pub cpu_weight: Option<u32>,
+    /// `cpu.max`, `"$MAX $PERIOD"` in microseconds.
+    pub cpu_max: Option<String>,
+}
+
+impl Default for LinuxConfig {
+    fn default() -> Self {
+        Self {
+            sched_idle: true,
+            nice: 19,
+            ioprio_idle: true,
+            cgroup: None,
+        }
+    }
+}
+
+/// A `[[rule]]` table: which processes to match, and what to do with them.
+///
+/// ```toml
+/// [[rule]]
+/// names = ["7z.exe"]
+/// action = "high"
+///
+/// [[rule]]
+/// names = ["setup.tmp"]
+/// # also throttle children and grandchildren
+/// inherit_depth = 2
+///
+/// [[rule]]
+/// # only the ones shipped with installers under D:/Games
+/// names = ["rz.exe"]
+/// paths = ["D:/Games/**"]
+/// parents = ["setup.tmp"]
+///
+/// [[rule]]
+/// # throttle the workers only, the UI thread keeps its QoS
+/// names = ["unarc.exe"]
+/// threads = ["worker-*"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rule {
+    /// Process names or name globs.
+    pub names: Vec<String>,
+    /// Process name regexes.
+    #[cfg(feature = "regex")]
+    pub regex: Vec<String>,
+    /// Executable path globs.
+    pub paths: Vec<String>,
+    /// Command line regexes.
+    #[cfg(feature = "regex")]
+    pub command_line_regex: Vec<String>,
+    /// Names of the parent process.
+    pub parents: Vec<String>,
+    pub action: Action,
+    /// Overrides [`Config::inherit_depth`] for this rule.
+    pub inherit_depth: Option<u32>,
+    /// Thread names or name globs, the action only applies to these threads
+    /// of matched processes if set.
+    pub threads: Vec<String>,
+}
+
+/// Short description for logs, e.g. `names=["7z.exe"] action=high`.
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut conditions = vec![("names", &self.names)];
+        #[cfg(feature = "regex")]
+        conditions.push(("regex", &self.regex));
+        conditions.push(("paths", &self.paths));
+        #[cfg(feature = "regex")]
+        conditions.push(("command_line_regex", &self.command_line_regex));
+        conditions.push(("parents", &self.parents));
+
+        for (key, values) in conditions {
+            if !values.is_empty() {
+                write!(f, "{key}={values:?} ")?;
+            }
+        }
+        write!(f, "action={}", self.action)?;
+        if let Some(depth) = self.inherit_depth {
+            write!(f, " inherit_depth={depth}")?;
+        }
+        if !self.threads.is_empty() {
+            write!(f, " threads={:?}", self.threads)?;
+        }
+        Ok(())
+    }
+}
+
+/// What to do with a matched process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Enable EcoQoS, with idle priority.
+    #[default]
+    Eco,
+    /// Force HighQoS, with normal priority.
+    High,
+    /// Let system decide, with normal priority.
+    #[serde(alias = "default")]
+    #[value(alias = "default")]
+    Restore,
+    /// Leave the process untouched.
+    // for rules only, `apply --action ignore` would do nothing
+    #[value(skip)]
+    Ignore,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Eco => "eco",
+            Self::High => "high",
+            Self::Restore => "restore",
+            Self::Ignore => "ignore",
+        })
+    }
+}
+
+impl Action {
+    /// The `enable` argument of `win32_ecoqos::process::toggle_efficiency_mode`,
+    /// `None` for [`Action::Ignore`].
+    pub fn efficiency_mode(self) -> Option<Option<bool>> {
+        match self {
+            Self::Eco => Some(Some(true)),
+            Self::High => Some(Some(false)),
+            Self::Restore => Some(None),
+            Self::Ignore => None,
+        }
+    }
 }
 
 impl Config {
-    pub fn from_default_path() -> Result<Self, Error> {
+    /// `config.toml` in the platform config directory, which is created if missing.
+    pub fn default_path() -> Result<PathBuf, Error> {
         let project_conf_dir = directories::ProjectDirs::from(
             "io",
             env!("CARGO_PKG_AUTHORS").split(",").next().unwrap(),
@@ -27,14 +221,148 @@ impl Config {
         let config_dir = project_conf_dir.config_dir();
         fs::create_dir_all(config_dir)?;
 
-        let conf = config_dir.join("config.toml");
-        if conf.exists() {
-            Ok(toml::from_str(&fs::read_to_string(conf)?)?)
-        } else {
-            warn!("{} not found, using default", conf.to_string_lossy());
-            Ok(Self::default())
+        Ok(config_dir.join("config.toml"))
+    }
+
+    /// write [`Config::default_toml`] to `path`, creating its directory,
+    /// returns `false` if the file already exists, which is left untouched.
+    pub fn write_default(path: impl AsRef<Path>) -> Result<bool, Error> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
         }
+
+        let mut file = match fs::File::create_new(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        file.write_all(Self::default_toml().as_bytes())?;
+        Ok(true)
+    }
+
+    /// The default config, documented with comments.
+    ///
+    /// It parses to [`Config::default`]. Without the `regex` feature,
+    /// `blacklist_regex` is commented out.
+    pub fn default_toml() -> String {
+        let Self {
+            blacklist,
+            #[cfg(feature = "regex")]
+            blacklist_regex,
+            case_sensitive,
+            ignore_exe_suffix,
+            inherit_depth,
+            linux:
+                LinuxConfig {
+                    sched_idle,
+                    nice,
+                    ioprio_idle,
+                    ..
+                },
+            windows: WindowsConfig {
+                ignore_timer_resolution,
+            },
+            ..
+        } = Self::default();
+
+        let mut toml = String::from(
+            "\
+# fitgirl-ecoqos config
+#
+# Processes matching a rule are throttled as soon as they start.
+# Edits apply to new processes without a restart,
+# check them with `fitgirl-ecoqos check-config`.
+
+# Processes to throttle, by name, or by glob over the name: `*` matches
+# any characters, `?` a single one, e.g. `cls-magic2*_x??.exe`.
+",
+        );
+        toml.push_str(&array("blacklist", &blacklist));
+
+        toml.push_str("\n# Processes to throttle, by regex over the name, unanchored.\n");
+        #[cfg(feature = "regex")]
+        toml.push_str(&array("blacklist_regex", &blacklist_regex));
+        #[cfg(not(feature = "regex"))]
+        toml.push_str(
+            "\
+# Needs a build with the `regex` feature.
+# blacklist_regex = ['re-.*\\.exe']
+",
+        );
+
+        let _ = write!(
+            toml,
+            r#"
+# Compare names, globs and regexes as-is, instead of ignoring case.
+case_sensitive = {case_sensitive}
+# Match names with or without their `.exe` suffix, `xtool` matches `xtool.exe`.
+ignore_exe_suffix = {ignore_exe_suffix}
+
+# Generations of descendants of a matched process which get its action too,
+# whatever their names. Rules may override it.
+inherit_depth = {inherit_depth}
+
+# Rules are checked in order before the blacklists, the first matching one wins.
+# A rule matches when one of `names`/`regex` matches the process name, and every
+# other condition it sets holds.
+#
+# [[rule]]
+# names = ["7z.exe"]
+# regex = ['^unarc.*\.exe$']
+# # executable path globs, `*` crosses directories
+# paths = ["D:/Games/**"]
+# command_line_regex = ['\s-d\b']
+# # names of the parent process
+# parents = ["setup.tmp"]
+# # eco (default), high, restore, or ignore
+# action = "high"
+# inherit_depth = 2
+# # only apply the action to these threads, by name or glob, e.g. to
+# # throttle workers while the UI thread keeps its QoS
+# threads = ["worker-*"]
+
+# How EcoQoS is approximated on Linux, ignored on Windows.
+[linux]
+# Use the `SCHED_IDLE` policy.
+sched_idle = {sched_idle}
+# Nice value, from -20 to 19.
+nice = {nice}
+# Use the idle I/O scheduling class.
+ioprio_idle = {ioprio_idle}
+
+# Move throttled processes into a cgroup v2, created if missing.
+# [linux.cgroup]
+# path = "/sys/fs/cgroup/fitgirl-ecoqos"
+# # from 1 to 10000
+# cpu_weight = 10
+# # "$MAX $PERIOD" in microseconds
+# cpu_max = "200000 100000"
+
+# How EcoQoS is applied on Windows, ignored on Linux.
+[windows]
+# Also ignore timer resolution requests of throttled processes,
+# so they can't keep the system on high-resolution timers. Windows 11 or newer.
+ignore_timer_resolution = {ignore_timer_resolution}
+"#
+        );
+        toml
+    }
+}
+
+/// a multi-line TOML array of strings, backslashes are kept as-is in literal strings.
+fn array(key: &str, values: &[String]) -> String {
+    let mut toml = format!("{key} = [\n");
+    for value in values {
+        let value = if value.contains('\\') && !value.contains(['\'', '\n']) {
+            format!("'{value}'")
+        } else {
+            toml::Value::String(value.clone()).to_string()
+        };
+        let _ = writeln!(toml, "    {value},");
     }
+    toml.push_str("]\n");
+    toml
 }
 
 impl Default for Config {
@@ -60,6 +388,12 @@ impl Default for Config {
                 .into_iter()
                 .map(str::to_string)
                 .collect(),
+            rules: vec![],
+            case_sensitive: false,
+            ignore_exe_suffix: false,
+            inherit_depth: 0,
+            linux: LinuxConfig::default(),
+            windows: WindowsConfig::default(),
         }
     }
 }
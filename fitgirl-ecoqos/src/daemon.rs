@@ -0,0 +1,544 @@
+use std::{fmt, pin::pin, time::Duration};
+
+use futures_lite::{Stream, StreamExt, future, stream};
+use listen_new_proc::{ProcessEvent, ProcessTable, StopHandle};
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    Error,
+    backend::{ProcessSource, QosController},
+    config::Action,
+    matcher::{Matcher, RuleMatch, Target, ThreadNames},
+};
+
+/// How often threads of processes matched by rules with `threads` are listed again,
+/// to catch threads started or named after the process was found.
+pub const THREAD_RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A process the daemon has applied an action to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tracked {
+    pub name: String,
+    pub action: Action,
+    /// generations of descendants which inherit `action`.
+    pub inherit_depth: u32,
+}
+
+/// What happened to processes found by [`Daemon::sweep`], or since the daemon started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// processes enumerated.
+    pub scanned: usize,
+    /// processes matched by a rule, including ignored ones.
+    pub matched: usize,
+    /// processes an action was applied to.
+    pub applied: usize,
+    /// processes an action failed to apply to.
+    pub failed: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            scanned,
+            matched,
+            applied,
+            failed,
+        } = self;
+        write!(
+            f,
+            "scanned {scanned} processes, {matched} matched, {applied} applied, {failed} failed"
+        )
+    }
+}
+
+impl Summary {
+    fn record(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Unmatched => return,
+            Outcome::Ignored => {}
+            Outcome::Applied(_) => self.applied += 1,
+            Outcome::Failed(_) => self.failed += 1,
+        }
+        self.matched += 1;
+    }
+}
+
+/// What happened to processes put back by [`Daemon::restore_all`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Restored {
+    pub restored: usize,
+    /// processes which failed to restore, most likely exited meanwhile.
+    pub failed: usize,
+}
+
+impl fmt::Display for Restored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { restored, failed } = self;
+        write!(f, "restored {restored} processes, {failed} failed")
+    }
+}
+
+/// Result of handling a single process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Unmatched,
+    Ignored,
+    Applied(Action),
+    Failed(Action),
+}
+
+impl Outcome {
+    fn action(self) -> Option<Action> {
+        match self {
+            Self::Unmatched => None,
+            Self::Ignored => Some(Action::Ignore),
+            Self::Applied(action) | Self::Failed(action) => Some(action),
+        }
+    }
+}
+
+/// Threads of a tracked process which its action is limited to.
+#[derive(Debug, Clone)]
+struct ThreadTargets<S> {
+    names: ThreadNames,
+    action: Action,
+    enable: Option<bool>,
+    /// thread ids the action was applied to, with their state from before.
+    applied: Vec<(u32, S)>,
+}
+
+/// Applies matched rules to new processes.
+#[derive(Debug)]
+pub struct Daemon<C: QosController> {
+    matcher: Matcher,
+    controller: C,
+    tracked: ProcessTable<Tracked>,
+    /// state of tracked processes before the daemon changed them.
+    saved: ProcessTable<C::Saved>,
+    /// names of every running process, to match rules on parent names.
+    names: ProcessTable<String>,
+    /// tracked processes whose action is limited to some threads.
+    threads: ProcessTable<ThreadTargets<C::SavedThread>>,
+    /// unmatched processes created before their parent was seen, matched again once it is.
+    orphans: ProcessTable<ProcessEvent>,
+    /// every process handled so far.
+    totals: Summary,
+}
+
+impl<C: QosController> Daemon<C> {
+    pub fn new(matcher: Matcher, controller: C) -> Self {
+        Self {
+            matcher,
+            controller,
+            tracked: ProcessTable::new(),
+            saved: ProcessTable::new(),
+            names: ProcessTable::new(),
+            threads: ProcessTable::new(),
+            orphans: ProcessTable::new(),
+            totals: Summary::default(),
+        }
+    }
+
+    /// processes with an applied action, which have not exited yet.
+    pub fn tracked(&self) -> &ProcessTable<Tracked> {
+        &self.tracked
+    }
+
+    /// what happened to every process found by sweeps and events so far.
+    pub fn summary(&self) -> Summary {
+        self.totals
+    }
+
+    /// handle a process event, returns the action of the matched rule.
+    ///
+    /// Only creation events are matched, exit events forget tracked processes.
+    /// A process reported before its parent is matched again once the parent is,
+    /// to inherit its action or match `parents`.
+    pub fn handle(&mut self, event: &ProcessEvent) -> Option<Action> {
+        if let Some(Tracked { name, action, .. }) = self.tracked.observe(event) {
+            debug!(
+                "forget process: {}, name: {name}, was {action:?}",
+                event.process_id
+            );
+        }
+        // creation events for a known PID are most likely an `exec`, which keeps what we changed
+        if event.is_exited() {
+            self.saved.observe(event);
+        }
+        self.names.observe(event);
+        self.threads.observe(event);
+        self.orphans.observe(event);
+        if !event.is_created() {
+            // its creation won't be reported anymore
+            for pid in self.orphans_of(event.process_id) {
+                self.orphans.remove(pid);
+            }
+            return None;
+        }
+        self.names.insert(event.process_id, event.name.clone());
+
+        let outcome = self.apply(event);
+        self.totals.scanned += 1;
+        self.totals.record(outcome);
+        if outcome == Outcome::Unmatched && self.parent_unseen(event) {
+            debug!(
+                "process: {}, parent: {} not seen yet",
+                event.process_id, event.parent_process_id
+            );
+            self.orphans.insert(event.process_id, event.clone());
+        }
+        self.adopt(event.process_id);
+        outcome.action()
+    }
+
+    /// whether the creation of the parent of `process` may still be reported.
+    fn parent_unseen(&self, process: &ProcessEvent) -> bool {
+        let parent = process.parent_process_id;
+        parent != 0 && (!self.names.contains(parent) || self.orphans.contains(parent))
+    }
+
+    fn orphans_of(&self, parent: u32) -> Vec<u32> {
+        self.orphans
+            .iter()
+            .filter(|(_, orphan)| orphan.parent_process_id == parent)
+            .map(|(pid, _)| pid)
+            .collect()
+    }
+
+    /// match again orphans of `pid`, now that it was seen.
+    ///
+    /// Those still unmatched keep waiting while `pid` is an orphan itself, in case it
+    /// inherits an action once adopted. The others get their own orphans matched again.
+    fn adopt(&mut self, pid: u32) {
+        let mut parents = vec![pid];
+        while let Some(parent) = parents.pop() {
+            for pid in self.orphans_of(parent) {
+                let Some(orphan) = self.orphans.remove(pid) else {
+                    continue;
+                };
+                let outcome = self.apply(&orphan);
+                self.totals.record(outcome);
+                if outcome == Outcome::Unmatched && self.parent_unseen(&orphan) {
+                    self.orphans.insert(pid, orphan);
+                } else {
+                    parents.push(pid);
+                }
+            }
+        }
+    }
+
+    /// apply rules to processes which were running before the daemon started.
+    ///
+    /// Children may be listed before their parents, unmatched processes are
+    /// retried while inherited actions keep spreading.
+    pub fn sweep(&mut self, processes: &[ProcessEvent]) -> Summary {
+        let mut summary = Summary {
+            scanned: processes.len(),
+            ..Default::default()
+        };
+        for process in processes {
+            self.names.insert(process.process_id, process.name.clone());
+        }
+
+        let mut pending: Vec<_> = processes.iter().collect();
+        loop {
+            let before = pending.len();
+            pending.retain(|process| {
+                let outcome = self.apply(process);
+                summary.record(outcome);
+                outcome == Outcome::Unmatched
+            });
+            if pending.len() == before {
+                break;
+            }
+        }
+
+        let totals = &mut self.totals;
+        totals.scanned += summary.scanned;
+        totals.matched += summary.matched;
+        totals.applied += summary.applied;
+        totals.failed += summary.failed;
+        summary
+    }
+
+    /// the rule matching a process, or the one inherited from its parent.
+    fn find(&self, process: &ProcessEvent) -> Option<RuleMatch> {
+        let mut target = Target::from_event(process);
+        target.parent_name = self
+            .names
+            .get(process.parent_process_id)
+            .map(String::as_str);
+        if let Some(rule) = self.matcher.find_rule(&target) {
+            return Some(rule);
+        }
+
+        let parent = self.tracked.get(process.parent_process_id)?;
+        let inherit_depth = parent.inherit_depth.checked_sub(1)?;
+        debug!(
+            "process: {}, inherits {:?} from parent: {}, name: {}",
+            process.process_id, parent.action, process.parent_process_id, parent.name
+        );
+        Some(RuleMatch {
+            action: parent.action,
+            inherit_depth,
+            threads: self
+                .threads
+                .get(process.parent_process_id)
+                .map(|targets| targets.names.clone()),
+        })
+    }
+
+    fn apply(&mut self, process: &ProcessEvent) -> Outcome {
+        let ProcessEvent {
+            process_id, name, ..
+        } = process;
+
+        let Some(RuleMatch {
+            action,
+            inherit_depth,
+            threads,
+        }) = self.find(process)
+        else {
+            return Outcome::Unmatched;
+        };
+        let Some(enable) = action.efficiency_mode() else {
+            info!("found process: {process_id}, name: {name}, ignored");
+            return Outcome::Ignored;
+        };
+
+        // keep the state from before the first action, if applied again
+        let first = !self.saved.contains(*process_id);
+        if first {
+            match self.controller.save(*process_id) {
+                Ok(saved) => {
+                    self.saved.insert(*process_id, saved);
+                }
+                Err(e) => warn!("failed to save state of {process_id}, it won't be restored: {e}"),
+            }
+        }
+
+        let result = match threads {
+            Some(names) => {
+                info!(
+                    "found process: {process_id}, name: {name}, applying {action:?} to threads {:?}...",
+                    names.patterns()
+                );
+                let mut targets = ThreadTargets {
+                    names,
+                    action,
+                    enable,
+                    applied: vec![],
+                };
+                self.apply_threads(*process_id, &mut targets).map(|_| {
+                    self.threads.insert(*process_id, targets);
+                })
+            }
+            None => {
+                info!("found process: {process_id}, name: {name}, applying {action:?}...");
+                self.controller.apply(*process_id, enable)
+            }
+        };
+        match result {
+            Ok(()) => {
+                let tracked = Tracked {
+                    name: name.clone(),
+                    action,
+                    inherit_depth,
+                };
+                self.tracked.insert(*process_id, tracked);
+                Outcome::Applied(action)
+            }
+            Err(e) => {
+                error!("failed to apply {action:?} to {process_id}: {e}");
+                if first {
+                    self.saved.remove(*process_id);
+                }
+                Outcome::Failed(action)
+            }
+        }
+    }
+
+    /// apply the action of `targets` to matching threads of `pid` it was not applied to yet,
+    /// returns how many there were.
+    ///
+    /// Threads which fail are logged and retried on the next call,
+    /// exited ones are forgotten, in case their id is reused.
+    fn apply_threads(
+        &self,
+        pid: u32,
+        targets: &mut ThreadTargets<C::SavedThread>,
+    ) -> Result<usize, Error> {
+        let threads = self.controller.threads(pid)?;
+        targets
+            .applied
+            .retain(|(applied, _)| threads.iter().any(|(tid, _)| tid == applied));
+
+        let mut applied = 0;
+        for (tid, name) in threads {
+            if targets.applied.iter().any(|(applied, _)| *applied == tid)
+                || !targets.names.is_match(&name)
+            {
+                continue;
+            }
+            let saved = match self.controller.save_thread(pid, tid) {
+                Ok(saved) => saved,
+                Err(e) => {
+                    warn!("failed to save state of thread {tid} of {pid}, skipped: {e}");
+                    continue;
+                }
+            };
+            match self.controller.apply_thread(pid, tid, targets.enable) {
+                Ok(()) => {
+                    debug!(
+                        "applied {:?} to thread: {tid}, name: {name}, of process: {pid}",
+                        targets.action
+                    );
+                    targets.applied.push((tid, saved));
+                    applied += 1;
+                }
+                Err(e) => warn!(
+                    "failed to apply {:?} to thread {tid} of {pid}: {e}",
+                    targets.action
+                ),
+            }
+        }
+        Ok(applied)
+    }
+
+    /// apply rules with `threads` again to their processes, for threads which
+    /// were started or named since, returns how many threads were changed.
+    ///
+    /// Called every [`THREAD_RESCAN_INTERVAL`] by [`Daemon::run_with_reload`].
+    pub fn rescan_threads(&mut self) -> usize {
+        let mut applied = 0;
+        let pids: Vec<_> = self.threads.iter().map(|(pid, _)| pid).collect();
+        for pid in pids {
+            let Some(mut targets) = self.threads.remove(pid) else {
+                continue;
+            };
+            match self.apply_threads(pid, &mut targets) {
+                Ok(count) => applied += count,
+                // most likely exited, the exit event forgets it
+                Err(e) => debug!("failed to list threads of {pid}: {e}"),
+            }
+            self.threads.insert(pid, targets);
+        }
+        applied
+    }
+
+    /// put every process the daemon changed back as it was, e.g. before exiting.
+    ///
+    /// Threads the daemon changed are restored first, then processes. Restored processes are no longer tracked, failures are logged.
+    pub fn restore_all(&mut self) -> Restored {
+        let pids: Vec<_> = self.threads.iter().map(|(pid, _)| pid).collect();
+        for pid in pids {
+            let Some(targets) = self.threads.remove(pid) else {
+                continue;
+            };
+            for (tid, saved) in targets.applied {
+                match self.controller.restore_thread(pid, tid, &saved) {
+                    Ok(()) => debug!("restored thread: {tid} of process: {pid} to {saved:?}"),
+                    Err(e) => debug!("failed to restore thread {tid} of {pid}: {e}"),
+                }
+            }
+        }
+
+        let mut restored = Restored::default();
+        let pids: Vec<_> = self.saved.iter().map(|(pid, _)| pid).collect();
+        for pid in pids {
+            let Some(saved) = self.saved.remove(pid) else {
+                continue;
+            };
+            self.tracked.remove(pid);
+            match self.controller.restore(pid, &saved) {
+                Ok(()) => {
+                    debug!("restored process: {pid} to {saved:?}");
+                    restored.restored += 1;
+                }
+                Err(e) => {
+                    warn!("failed to restore {pid}: {e}");
+                    restored.failed += 1;
+                }
+            }
+        }
+        restored
+    }
+
+    /// swap the rule set, for processes created from now on.
+    ///
+    /// Tracked processes keep the action they were given.
+    pub fn reload(&mut self, matcher: Matcher) {
+        self.matcher = matcher;
+    }
+
+    /// apply rules to running processes, then handle every event from `source`, until it ends.
+    pub async fn run(&mut self, source: &mut impl ProcessSource) -> Result<(), Error> {
+        self.run_with_reload(source, stream::pending(), &StopHandle::new())
+            .await
+    }
+
+    /// like [`Daemon::run`], and swap the rule set whenever `updates` yields one,
+    /// until `stop` is requested.
+    ///
+    /// On stop, events already received are handled before returning.
+    /// Changed processes are left as they are, see [`Daemon::restore_all`].
+    pub async fn run_with_reload(
+        &mut self,
+        source: &mut impl ProcessSource,
+        updates: impl Stream<Item = Matcher>,
+        stop: &StopHandle,
+    ) -> Result<(), Error> {
+        // subscribe first, not to miss processes created during the sweep
+        let mut events = source.events()?;
+
+        let summary = self.sweep(&source.snapshot()?);
+        info!("startup sweep: {summary}");
+
+        let mut updates = pin!(updates);
+        let mut rescan = tokio::time::interval_at(
+            Instant::now() + THREAD_RESCAN_INTERVAL,
+            THREAD_RESCAN_INTERVAL,
+        );
+        rescan.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                biased;
+                () = stop.stopped() => {
+                    self.drain(&mut events).await?;
+                    break;
+                }
+                event = events.next() => match event {
+                    Some(event) => {
+                        self.handle(&event?);
+                    }
+                    None => break,
+                },
+                Some(matcher) = updates.next() => self.reload(matcher),
+                _ = rescan.tick(), if !self.threads.is_empty() => {
+                    let applied = self.rescan_threads();
+                    if applied > 0 {
+                        debug!("thread rescan: applied to {applied} threads");
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// handle events which are ready, without waiting for more.
+    async fn drain(
+        &mut self,
+        events: &mut (impl Stream<Item = Result<ProcessEvent, Error>> + Unpin),
+    ) -> Result<(), Error> {
+        let mut drained = 0;
+        while let Some(Some(event)) = future::poll_once(events.next()).await {
+            self.handle(&event?);
+            drained += 1;
+        }
+        debug!("handled {drained} pending events before stopping");
+        Ok(())
+    }
+}
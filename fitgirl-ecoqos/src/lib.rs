@@ -1,4 +1,5 @@
 use thiserror::Error;
+#[cfg(windows)]
 use win32_ecoqos::windows_result;
 
 #[derive(Debug, Error)]
@@ -7,16 +8,46 @@ pub enum Error {
     InitConfigFailed(&'static str),
     #[error("Toml deserialization: {0}")]
     Toml(#[from] toml::de::Error),
+    #[error("{}: {}", .0.to_string_lossy(), .1)]
+    ConfigFile(std::path::PathBuf, String),
+    #[error("invalid merged config: {0}")]
+    InvalidMerge(String),
+    #[error("Toml serialization: {0}")]
+    TomlSer(#[from] toml::ser::Error),
     #[error("IO Error: {0}")]
     IOError(#[from] std::io::Error),
+    #[error("no process matches {0}")]
+    NoProcess(String),
     #[error("Listen Error: {0}")]
     Listen(#[from] listen_new_proc::Error),
+    #[cfg(windows)]
     #[error("Win32 error: {0}")]
     Win32(#[from] windows_result::Error),
+    #[cfg(windows)]
+    #[error("service error: {0}")]
+    Service(#[from] windows_service::Error),
+    #[cfg(windows)]
+    #[error("schtasks failed: {0}")]
+    Schtasks(String),
+    #[error("service can't go from {0:?} to {1:?}")]
+    ServiceTransition(service::ServiceState, service::ServiceState),
 
+    #[error("invalid glob: {0}")]
+    Glob(#[from] globset::Error),
+    #[error("{0:?} matches every name once `.exe` is ignored")]
+    CatchAllGlob(String),
     #[cfg(feature = "regex")]
     #[error("invalid regex: {0}")]
     Regex(#[from] regex::Error),
 }
 
+pub mod autostart;
+pub mod backend;
+pub mod check;
+pub mod cli;
 pub mod config;
+pub mod daemon;
+pub mod loader;
+pub mod matcher;
+pub mod reload;
+pub mod service;
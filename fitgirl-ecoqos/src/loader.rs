@@ -0,0 +1,284 @@
+use std::{collections::BTreeMap, fmt, fs, io::ErrorKind, path::PathBuf};
+
+use toml::{Table, Value};
+use tracing::debug;
+
+use crate::{Error, config::Config};
+
+/// Prefix of environment overrides.
+///
+/// The rest of the variable name is the lowercased key, with `__` between
+/// nested keys: `FITGIRL_ECOQOS_LINUX__NICE=10` sets `nice` in `[linux]`.
+/// Values are parsed as TOML, or taken as a string if that fails.
+pub const ENV_PREFIX: &str = "FITGIRL_ECOQOS_";
+
+/// Where a config value comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    /// name of the environment variable.
+    Env(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::File(path) => write!(f, "{}", path.to_string_lossy()),
+            Self::Env(name) => write!(f, "env {name}"),
+        }
+    }
+}
+
+/// A config file to merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layer {
+    pub path: PathBuf,
+    /// fail if the file doesn't exist, instead of skipping it.
+    pub required: bool,
+}
+
+/// Config merged from every source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layered {
+    pub config: Config,
+    /// dotted key -> where its value comes from.
+    ///
+    /// Arrays are replaced as a whole, `rule` or `blacklist` have a single origin.
+    pub origins: BTreeMap<String, Origin>,
+}
+
+/// Loads a config from layers, each one overriding the previous ones:
+///
+/// 1. defaults
+/// 2. config files, in the order they were added
+/// 3. `FITGIRL_ECOQOS_*` environment variables, see [`ENV_PREFIX`]
+///
+/// Tables are merged key by key, any other value, arrays included, is replaced.
+#[derive(Debug, Clone, Default)]
+pub struct Loader {
+    layers: Vec<Layer>,
+    env: Vec<(String, String)>,
+}
+
+impl Loader {
+    /// a loader with defaults only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the system-wide file, the per-user file, and `config` if given, which must exist.
+    pub fn standard(config: Option<PathBuf>) -> Result<Self, Error> {
+        let mut loader = Self::new();
+        if let Some(path) = Self::system_path() {
+            loader = loader.with_file(path);
+        }
+        loader = loader.with_file(Config::default_path()?);
+        if let Some(path) = config {
+            loader = loader.with_required_file(path);
+        }
+        Ok(loader)
+    }
+
+    /// `%ProgramData%\fitgirl-ecoqos\config.toml` on Windows,
+    /// `/etc/fitgirl-ecoqos/config.toml` elsewhere.
+    pub fn system_path() -> Option<PathBuf> {
+        let dir = if cfg!(windows) {
+            PathBuf::from(std::env::var_os("ProgramData")?)
+        } else {
+            PathBuf::from("/etc")
+        };
+        Some(dir.join(env!("CARGO_PKG_NAME")).join("config.toml"))
+    }
+
+    /// merge a file, skipped if it doesn't exist.
+    pub fn with_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        self.layers.push(Layer {
+            path,
+            required: false,
+        });
+        self
+    }
+
+    /// merge a file, which must exist.
+    pub fn with_required_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        self.layers.push(Layer {
+            path,
+            required: true,
+        });
+        self
+    }
+
+    /// take overrides from environment variables, usually `std::env::vars()`.
+    ///
+    /// Variables without [`ENV_PREFIX`] are ignored.
+    pub fn with_env(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut env: Vec<_> = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX) && name.len() > ENV_PREFIX.len())
+            .collect();
+        // stable precedence between variables setting the same key
+        env.sort();
+        self.env = env;
+        self
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// read every file, `None` for missing optional ones.
+    pub fn read(&self) -> Result<Vec<Option<String>>, Error> {
+        self.layers
+            .iter()
+            .map(|Layer { path, required }| match fs::read_to_string(path) {
+                Ok(contents) => Ok(Some(contents)),
+                Err(e) if e.kind() == ErrorKind::NotFound && !required => {
+                    debug!("{} not found, skipped", path.to_string_lossy());
+                    Ok(None)
+                }
+                Err(e) => Err(Error::ConfigFile(path.clone(), e.to_string())),
+            })
+            .collect()
+    }
+
+    /// read and merge every layer.
+    pub fn load(&self) -> Result<Layered, Error> {
+        self.merge(&self.read()?)
+    }
+
+    /// merge contents returned by [`Loader::read`].
+    pub fn merge(&self, contents: &[Option<String>]) -> Result<Layered, Error> {
+        let mut merged = Table::try_from(Config::default())?;
+        let mut origins = BTreeMap::new();
+        record(&merged, "", &Origin::Default, &mut origins);
+
+        for (Layer { path, .. }, contents) in self.layers.iter().zip(contents) {
+            let Some(contents) = contents else {
+                continue;
+            };
+            let table: Table = toml::from_str(contents)
+                .map_err(|e| Error::ConfigFile(path.clone(), e.to_string()))?;
+            let origin = Origin::File(path.clone());
+            merge(&mut merged, table, "", &origin, &mut origins);
+        }
+
+        for (name, value) in &self.env {
+            let mut table = Table::new();
+            let mut keys = name[ENV_PREFIX.len()..].to_lowercase();
+            let mut value = parse_env(value);
+            while let Some((parents, key)) = keys.rsplit_once("__") {
+                value = Value::Table(Table::from_iter([(key.to_string(), value)]));
+                keys = parents.to_string();
+            }
+            table.insert(keys, value);
+            merge(
+                &mut merged,
+                table,
+                "",
+                &Origin::Env(name.clone()),
+                &mut origins,
+            );
+        }
+
+        let config = Value::Table(merged.clone())
+            .try_into()
+            .map_err(|e: toml::de::Error| {
+                let message = e.message().trim_end();
+                Error::InvalidMerge(match culprit(&merged, &origins) {
+                    Some(culprit) => format!("{culprit}: {message}"),
+                    None => message.to_string(),
+                })
+            })?;
+        Ok(Layered { config, origins })
+    }
+}
+
+/// a TOML value, or a plain string.
+fn parse_env(value: &str) -> Value {
+    toml::from_str::<Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(value.to_string()))
+}
+
+/// the top-level key which fails to load, with where its values come from,
+/// e.g. `blacklist (env FITGIRL_ECOQOS_BLACKLIST)`.
+fn culprit(merged: &Table, origins: &BTreeMap<String, Origin>) -> Option<String> {
+    let (key, _) = merged.iter().find(|(key, value)| {
+        let table = Table::from_iter([(key.to_string(), (*value).clone())]);
+        Value::Table(table).try_into::<Config>().is_err()
+    })?;
+
+    let nested = format!("{key}.");
+    let mut sources: Vec<_> = origins
+        .iter()
+        .filter(|(path, _)| *path == key || path.starts_with(&nested))
+        .map(|(_, origin)| origin.to_string())
+        .collect();
+    sources.dedup();
+    Some(format!("{key} ({})", sources.join(", ")))
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+/// set the origin of every leaf of `table`.
+fn record(table: &Table, prefix: &str, origin: &Origin, origins: &mut BTreeMap<String, Origin>) {
+    for (key, value) in table {
+        let path = join(prefix, key);
+        match value {
+            Value::Table(table) => record(table, &path, origin, origins),
+            _ => {
+                origins.insert(path, origin.clone());
+            }
+        }
+    }
+}
+
+fn merge(
+    base: &mut Table,
+    layer: Table,
+    prefix: &str,
+    origin: &Origin,
+    origins: &mut BTreeMap<String, Origin>,
+) {
+    for (key, value) in layer {
+        let path = join(prefix, &key);
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(table)) => {
+                merge(base, table, &path, origin, origins);
+            }
+            (_, value) => {
+                let nested = format!("{path}.");
+                origins.retain(|key, _| key != &path && !key.starts_with(&nested));
+                match &value {
+                    Value::Table(table) => record(table, &path, origin, origins),
+                    _ => {
+                        origins.insert(path, origin.clone());
+                    }
+                }
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+impl Layered {
+    /// `key  origin` lines, sorted by key.
+    pub fn origins_table(&self) -> String {
+        let width = self.origins.keys().map(String::len).max().unwrap_or(0);
+        self.origins
+            .iter()
+            .map(|(key, origin)| format!("{key:<width$}  {origin}\n"))
+            .collect()
+    }
+}
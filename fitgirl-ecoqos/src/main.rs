@@ -1,67 +1,376 @@
-use ahash::AHashSet;
-use fitgirl_ecoqos::{Error, config::Config};
-use listen_new_proc::{Process, listen_process_creation};
-use tracing::{error, info, level_filters::LevelFilter, warn};
+#[cfg(any(windows, target_os = "linux"))]
+use std::{io, time::Duration};
+
+#[cfg(any(windows, target_os = "linux"))]
+use clap::Parser;
+#[cfg(target_os = "linux")]
+use fitgirl_ecoqos::backend::linux::{LinuxController, ProcSource};
+#[cfg(windows)]
+use fitgirl_ecoqos::backend::windows::{Win32Controller, WmiSource};
+#[cfg(any(windows, target_os = "linux"))]
+use fitgirl_ecoqos::{
+    Error, check,
+    cli::{self, Cli, Command},
+    config::{Action, Config},
+    daemon::Daemon,
+    loader::Loader,
+    matcher::{Matcher, Normalizer},
+    reload::Reloader,
+};
+#[cfg(any(windows, target_os = "linux"))]
+use listen_new_proc::StopHandle;
+#[cfg(windows)]
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    sync::Mutex,
+};
+#[cfg(any(windows, target_os = "linux"))]
+use tracing::{info, level_filters::LevelFilter, warn};
+#[cfg(any(windows, target_os = "linux"))]
 use tracing_subscriber::EnvFilter;
-use win32_ecoqos::{process::toggle_efficiency_mode, utils::Processes};
 
-#[cfg(feature = "regex")]
-use regex::RegexSet;
+#[cfg(windows)]
+use fitgirl_ecoqos::{autostart, service};
+#[cfg(windows)]
+use tracing::error;
+#[cfg(windows)]
+use win32_ecoqos::utils::Processes;
+#[cfg(windows)]
+use windows_service::service_dispatcher;
 
-#[tokio::main]
-async fn main() -> Result<(), Error> {
-    let my_pid = std::process::id();
-    let in_cmd = Processes::try_new()?
-        .find(|p| p.process_parent_id == my_pid)
-        .is_some_and(|p| p.process_name == "conhost.exe");
+#[cfg(not(any(windows, target_os = "linux")))]
+fn main() {
+    eprintln!("fitgirl-ecoqos only supports Windows and Linux!");
+    std::process::exit(1);
+}
 
-    tracing_subscriber::FmtSubscriber::builder()
-        .with_env_filter(
-            EnvFilter::builder()
-                .with_default_directive(LevelFilter::INFO.into())
-                .from_env_lossy(),
-        )
-        .with_ansi(!in_cmd)
-        .init();
+/// report problems of every config file, exit with 1 on errors.
+#[cfg(any(windows, target_os = "linux"))]
+fn check_config(loader: &Loader) -> Result<(), Error> {
+    let mut errors = false;
+    let mut warnings = 0;
+    for (layer, contents) in loader.layers().iter().zip(loader.read()?) {
+        let Some(source) = contents else {
+            continue;
+        };
+        let diagnostics = check::check(&source);
+
+        let path = layer.path.to_string_lossy();
+        for diagnostic in &diagnostics {
+            eprintln!("{path}:{diagnostic}");
+        }
+        errors |= check::has_errors(&diagnostics);
+        warnings += diagnostics.len();
+    }
+
+    // values from environment variables, and the merged result
+    if !errors && let Err(e) = loader.load() {
+        eprintln!("error: {e}");
+        errors = true;
+    }
+    if errors {
+        std::process::exit(1);
+    }
+    eprintln!("ok, {warnings} warnings");
+    Ok(())
+}
+
+#[cfg(windows)]
+fn source() -> WmiSource {
+    WmiSource
+}
+
+#[cfg(target_os = "linux")]
+fn source() -> ProcSource {
+    ProcSource
+}
+
+/// resolves on Ctrl+C, Ctrl+Break, or when the console window is closed.
+#[cfg(windows)]
+fn shutdown_signal() -> io::Result<impl Future<Output = &'static str>> {
+    use tokio::signal::windows;
+
+    let mut ctrl_c = windows::ctrl_c()?;
+    let mut ctrl_break = windows::ctrl_break()?;
+    let mut ctrl_close = windows::ctrl_close()?;
+    Ok(async move {
+        tokio::select! {
+            _ = ctrl_c.recv() => "Ctrl+C",
+            _ = ctrl_break.recv() => "Ctrl+Break",
+            _ = ctrl_close.recv() => "console close",
+        }
+    })
+}
+
+/// resolves on SIGINT, SIGTERM or SIGHUP.
+#[cfg(target_os = "linux")]
+fn shutdown_signal() -> io::Result<impl Future<Output = &'static str>> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    Ok(async move {
+        tokio::select! {
+            _ = interrupt.recv() => "SIGINT",
+            _ = terminate.recv() => "SIGTERM",
+            _ = hangup.recv() => "SIGHUP",
+        }
+    })
+}
+
+/// the daemon, with config reloads, until `stop` is requested.
+///
+/// `ready` is called once the rules and the controller are set up, before the startup sweep.
+/// Changed processes are restored before returning.
+#[cfg(any(windows, target_os = "linux"))]
+async fn run(
+    loader: Loader,
+    config: Config,
+    stop: &StopHandle,
+    ready: impl FnOnce() -> Result<(), Error>,
+) -> Result<(), Error> {
+    let files: Vec<_> = loader.layers().iter().map(|l| l.path.clone()).collect();
+    info!("config files: {files:?}");
+    info!("startup with config: {config:?}");
+    let matcher = Matcher::new(&config)?;
+    let controller = controller(&config)?;
+    ready()?;
+    let updates = Reloader::new(loader, config).watch(Duration::from_secs(2));
+
+    let mut daemon = Daemon::new(matcher, controller);
+    let result = daemon.run_with_reload(&mut source(), updates, stop).await;
+    // don't leave processes throttled, even if the event stream failed
+    let restored = daemon.restore_all();
+    info!("stopped: {}, {restored}", daemon.summary());
+    result
+}
+
+#[cfg(windows)]
+windows_service::define_windows_service!(ffi_service_main, service_main);
+
+/// entry point of the service, on a thread of the service dispatcher.
+#[cfg(windows)]
+fn service_main(_arguments: Vec<OsString>) {
+    let result = service::windows::run(|stop, ready| {
+        // launched with `service`, and `--config` if installed with it
+        let loader = Cli::parse().loader()?;
+        let config = loader.load()?.config;
+        tokio::runtime::Runtime::new()?.block_on(run(loader, config, &stop, ready))
+    });
+    if let Err(e) = result {
+        error!("service failed: {e}");
+    }
+}
 
+/// `service.log` next to the system-wide config, appended to.
+#[cfg(windows)]
+fn service_log() -> io::Result<File> {
+    let path = Loader::system_path()
+        .and_then(|path| Some(path.parent()?.join("service.log")))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "%ProgramData% is not set"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    File::options().create(true).append(true).open(path)
+}
+
+#[cfg(windows)]
+fn controller(config: &Config) -> Result<Win32Controller, Error> {
+    Ok(Win32Controller::new(config.windows.clone()))
+}
+
+#[cfg(target_os = "linux")]
+fn controller(config: &Config) -> Result<LinuxController, Error> {
+    LinuxController::new(config.linux.clone())
+}
+
+/// a controller for queries only, which changes nothing to set itself up.
+#[cfg(windows)]
+fn query_controller(config: &Config) -> Win32Controller {
+    Win32Controller::new(config.windows.clone())
+}
+
+#[cfg(target_os = "linux")]
+fn query_controller(config: &Config) -> LinuxController {
+    LinuxController::query_only(config.linux.clone())
+}
+
+/// EcoQoS requires Windows 11, or a late Windows 10 insider build.
+#[cfg(windows)]
+fn check_os_version() {
     let os_version = windows_version::OsVersion::current().build;
     assert!(os_version >= 21359, "EcoQoS not supported!");
 
     if os_version < 22621 {
         warn!("you may not get the best result before Windows 11 22H2!");
     }
+}
 
-    let config = Config::from_default_path()?;
-    info!("startup with config: {config:?}");
-    let Config {
-        blacklist,
-        #[cfg(feature = "regex")]
-        blacklist_regex,
-    } = config;
-    let blacklist: AHashSet<String> = blacklist.into_iter().collect();
-    #[cfg(feature = "regex")]
-    let regexset = RegexSet::new(blacklist_regex)?;
-
-    listen_process_creation(
-        move |Process {
-                  process_id, name, ..
-              }| {
-            if !blacklist.contains(&name) {
-                return;
-            }
+#[cfg(target_os = "linux")]
+fn check_os_version() {}
 
-            #[cfg(feature = "regex")]
-            if !regexset.is_match(&name) {
-                return;
-            }
+/// a documented file to start from: at `--config` if it's missing,
+/// else at the per-user file unless some config is already in place.
+#[cfg(any(windows, target_os = "linux"))]
+fn write_default_config(cli: &Cli, loader: &Loader) -> Result<(), Error> {
+    let missing = match &cli.config {
+        Some(path) => !path.exists(),
+        None => loader.read()?.iter().all(Option::is_none),
+    };
+    let path = cli.config_path()?;
+    match missing.then(|| Config::write_default(&path)) {
+        Some(Ok(true)) => info!("wrote default config to {}", path.to_string_lossy()),
+        Some(Err(e)) => warn!("failed to write default config: {e}"),
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(any(windows, target_os = "linux"))]
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let cli = Cli::parse();
+    let command = cli.command();
+
+    #[cfg(windows)]
+    let in_cmd = {
+        let my_pid = std::process::id();
+        Processes::try_new()?
+            .find(|p| p.process_parent_id == my_pid)
+            .is_some_and(|p| p.process_name == "conhost.exe")
+    };
+    #[cfg(not(windows))]
+    let in_cmd = false;
+
+    let subscriber = tracing_subscriber::FmtSubscriber::builder()
+        .with_env_filter(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::INFO.into())
+                .from_env_lossy(),
+        )
+        .with_ansi(!in_cmd);
+    match command {
+        Command::Run => subscriber.init(),
+        // no console to log to
+        #[cfg(windows)]
+        Command::Service => subscriber
+            .with_ansi(false)
+            .with_writer(Mutex::new(service_log()?))
+            .init(),
+        // stdout is left to the output of other commands
+        _ => subscriber.with_writer(io::stderr).init(),
+    }
 
-            info!("found process: {process_id}, name: {name}, throtting...");
-            if let Err(e) = toggle_efficiency_mode(process_id, Some(true)) {
-                error!("failed to throttle {process_id}: {e}");
+    let loader = cli.loader()?;
+    let mut out = io::stdout().lock();
+    match command {
+        Command::CheckConfig => check_config(&loader),
+        Command::PrintDefaultConfig => {
+            print!("{}", Config::default_toml());
+            Ok(())
+        }
+        Command::Init => {
+            let path = cli.config_path()?;
+            let path_str = path.to_string_lossy();
+            if Config::write_default(&path)? {
+                eprintln!("wrote default config to {path_str}");
+            } else {
+                eprintln!("{path_str} already exists, left untouched");
+            }
+            Ok(())
+        }
+        Command::PrintConfig { origins } => {
+            let layered = loader.load()?;
+            if origins {
+                print!("{}", layered.origins_table());
+            } else {
+                print!("{}", toml::to_string_pretty(&layered.config)?);
             }
-        },
-    )
-    .await?;
+            Ok(())
+        }
+        #[cfg(windows)]
+        Command::InstallService => {
+            check_os_version();
+            let config = cli.config.as_deref();
+            service::windows::install(&std::env::current_exe()?, config)
+        }
+        #[cfg(windows)]
+        Command::UninstallService => service::windows::uninstall(),
+        #[cfg(windows)]
+        Command::InstallAutostart => {
+            check_os_version();
+            let config = cli.config.as_deref();
+            autostart::windows::install(&std::env::current_exe()?, config)
+        }
+        #[cfg(windows)]
+        Command::UninstallAutostart => autostart::windows::uninstall(),
+        #[cfg(windows)]
+        Command::Service => {
+            check_os_version();
+            // blocks until the service stops, `service_main` runs on another thread
+            tokio::task::block_in_place(|| {
+                service_dispatcher::start(service::SERVICE_NAME, ffi_service_main)
+            })?;
+            Ok(())
+        }
+        Command::Run => {
+            check_os_version();
+            write_default_config(&cli, &loader)?;
+            let config = loader.load()?.config;
 
-    Ok(())
+            let stop = StopHandle::new();
+            let signal = shutdown_signal()?;
+            tokio::spawn({
+                let stop = stop.clone();
+                async move {
+                    info!("received {}, stopping...", signal.await);
+                    stop.stop();
+                }
+            });
+            run(loader, config, &stop, || Ok(())).await
+        }
+        Command::Apply { target, action } => {
+            check_os_version();
+            let config = loader.load()?.config;
+            let controller = controller(&config)?;
+            cli::apply(
+                &mut out,
+                &mut source(),
+                &controller,
+                Normalizer::new(&config),
+                &target,
+                action,
+            )
+        }
+        Command::Restore { target } => {
+            check_os_version();
+            let config = loader.load()?.config;
+            let controller = controller(&config)?;
+            cli::apply(
+                &mut out,
+                &mut source(),
+                &controller,
+                Normalizer::new(&config),
+                &target,
+                Action::Restore,
+            )
+        }
+        Command::Status { target } => {
+            check_os_version();
+            let config = loader.load()?.config;
+            cli::status(
+                &mut out,
+                &mut source(),
+                &query_controller(&config),
+                &Matcher::new(&config)?,
+                target.as_ref(),
+            )
+        }
+        Command::List => {
+            let matcher = Matcher::new(&loader.load()?.config)?;
+            cli::list(&mut out, &mut source(), &matcher)
+        }
+    }
 }
@@ -0,0 +1,548 @@
+use std::{borrow::Cow, cell::RefCell, path::Path, sync::Arc};
+
+use ahash::AHashMap;
+use globset::{Candidate, GlobBuilder, GlobSet, GlobSetBuilder};
+use listen_new_proc::ProcessEvent;
+
+#[cfg(feature = "regex")]
+use regex::{RegexSet, RegexSetBuilder};
+
+use crate::{
+    Error,
+    config::{Action, Config, Rule},
+};
+
+/// Precompiled rule set, built from [`Config`].
+///
+/// Each `[[rule]]` matches a name if it lists the name in `names`, **or** if
+/// any of its `regex` patterns matches it. `paths`, `command_line_regex` and
+/// `parents` narrow a rule down further, every one of them which is set must
+/// match too. Rules are checked in order and the first matching one wins.
+/// `blacklist` and `blacklist_regex` act as a final rule with [`Action::Eco`].
+///
+/// A rule may pass its action down to descendants of the matched process,
+/// see [`Config::inherit_depth`], or limit it to some of its threads,
+/// see [`ThreadNames`].
+///
+/// Names are compared after [`Normalizer::normalize`], case-insensitive by default.
+/// Names with `*` or `?`, e.g. `cls-magic2*_x??.exe`, match as globs,
+/// others are looked up in a hash map. Regex patterns are unanchored, use `^...$`
+/// to match the whole name, and see the name with its `.exe` suffix.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    normalizer: Normalizer,
+    /// normalized exact name -> indexes of the rules listing it
+    names: AHashMap<String, Vec<usize>>,
+    name_globs: GlobSet,
+    /// name glob index -> rule index
+    glob_rules: Vec<usize>,
+    #[cfg(feature = "regex")]
+    regexset: RegexSet,
+    /// regex pattern index -> rule index
+    #[cfg(feature = "regex")]
+    regex_rules: Vec<usize>,
+    filters: Vec<Filters>,
+    rules: Vec<RuleMatch>,
+}
+
+/// How process names are compared, from [`Config::case_sensitive`]
+/// and [`Config::ignore_exe_suffix`].
+///
+/// ```rust
+/// use fitgirl_ecoqos::matcher::Normalizer;
+///
+/// let normalizer = Normalizer {
+///     case_sensitive: false,
+///     ignore_exe_suffix: true,
+/// };
+/// assert_eq!(normalizer.normalize("XTool.EXE"), "xtool");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Normalizer {
+    pub case_sensitive: bool,
+    pub ignore_exe_suffix: bool,
+}
+
+impl Normalizer {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            case_sensitive: config.case_sensitive,
+            ignore_exe_suffix: config.ignore_exe_suffix,
+        }
+    }
+
+    /// lowercase the name unless case-sensitive, then strip `.exe` if ignored.
+    pub fn normalize<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        self.strip_suffix(match self.case_sensitive {
+            true => Cow::Borrowed(name),
+            false => Cow::Owned(name.to_lowercase()),
+        })
+    }
+
+    /// like [`Normalizer::normalize`], ASCII names fitting in `buf` are lowercased
+    /// into it rather than allocated.
+    pub fn normalize_in<'a>(&self, name: &'a str, buf: &'a mut [u8]) -> Cow<'a, str> {
+        match self.case_sensitive {
+            false if name.is_ascii() && name.len() <= buf.len() => {
+                let buf = &mut buf[..name.len()];
+                buf.copy_from_slice(name.as_bytes());
+                buf.make_ascii_lowercase();
+                let name = std::str::from_utf8(buf).expect("ASCII is valid UTF-8");
+                self.strip_suffix(Cow::Borrowed(name))
+            }
+            _ => self.normalize(name),
+        }
+    }
+
+    fn strip_suffix<'a>(&self, mut name: Cow<'a, str>) -> Cow<'a, str> {
+        if self.ignore_exe_suffix {
+            let len = name.len();
+            if len >= 4
+                && name.is_char_boundary(len - 4)
+                && name[len - 4..].eq_ignore_ascii_case(".exe")
+            {
+                match &mut name {
+                    Cow::Borrowed(borrowed) => *borrowed = &borrowed[..len - 4],
+                    Cow::Owned(owned) => owned.truncate(len - 4),
+                }
+            }
+        }
+        name
+    }
+}
+
+/// Whether a name is a glob, rather than an exact name.
+///
+/// Only `*` and `?` are wildcards, other glob characters are taken literally,
+/// e.g. `setup[1].exe`.
+pub fn is_glob(name: &str) -> bool {
+    name.contains(['*', '?'])
+}
+
+/// Stack buffer for names normalized by [`Matcher::find_rule`], longer ones are allocated.
+const NAME_BUF_LEN: usize = 256;
+
+thread_local! {
+    /// name glob indexes matched by [`Matcher::find_rule`], reused between calls.
+    static GLOB_MATCHES: RefCell<Vec<usize>> = const { RefCell::new(vec![]) };
+    /// whether each name regex matched, for [`Matcher::find_rule`].
+    #[cfg(feature = "regex")]
+    static REGEX_MATCHES: RefCell<Vec<bool>> = const { RefCell::new(vec![]) };
+}
+
+/// Rules matched by name, a bit per rule index, inline up to 256 rules.
+#[derive(Debug)]
+enum RuleBits {
+    Inline([u64; 4]),
+    Heap(Vec<u64>),
+}
+
+impl RuleBits {
+    fn new(rules: usize) -> Self {
+        match rules.div_ceil(64) {
+            words @ 5.. => Self::Heap(vec![0; words]),
+            _ => Self::Inline([0; 4]),
+        }
+    }
+
+    fn words(&self) -> &[u64] {
+        match self {
+            Self::Inline(words) => words,
+            Self::Heap(words) => words,
+        }
+    }
+
+    fn insert(&mut self, index: usize) {
+        let words = match self {
+            Self::Inline(words) => &mut words[..],
+            Self::Heap(words) => &mut words[..],
+        };
+        words[index / 64] |= 1 << (index % 64);
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.words()[index / 64] & (1 << (index % 64)) != 0
+    }
+}
+
+/// What a matched rule asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleMatch {
+    pub action: Action,
+    /// generations of descendants which inherit `action`.
+    pub inherit_depth: u32,
+    /// threads `action` is limited to, the whole process if `None`.
+    pub threads: Option<ThreadNames>,
+}
+
+/// Thread names of a rule, see [`Rule::threads`].
+///
+/// Names and globs match the whole thread name, ignoring case unless
+/// [`Config::case_sensitive`] is set. Clones share the compiled globs.
+///
+/// ```rust
+/// use fitgirl_ecoqos::matcher::ThreadNames;
+///
+/// let threads = ThreadNames::new(&["worker-*".to_string()], false).unwrap();
+/// assert!(threads.is_match("Worker-3"));
+/// assert!(!threads.is_match("ui"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ThreadNames {
+    patterns: Arc<[String]>,
+    globs: Arc<GlobSet>,
+}
+
+impl ThreadNames {
+    pub fn new(patterns: &[String], case_sensitive: bool) -> Result<Self, Error> {
+        let mut globs = GlobSetBuilder::new();
+        for pattern in patterns {
+            globs.add(thread_glob(pattern, case_sensitive)?);
+        }
+        Ok(Self {
+            patterns: patterns.into(),
+            globs: Arc::new(globs.build()?),
+        })
+    }
+
+    pub fn is_match(&self, thread_name: &str) -> bool {
+        self.globs.is_match(thread_name)
+    }
+
+    /// the names and globs, as configured.
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+}
+
+impl PartialEq for ThreadNames {
+    fn eq(&self, other: &Self) -> bool {
+        self.patterns == other.patterns
+    }
+}
+
+impl Eq for ThreadNames {}
+
+/// What rules are matched against.
+///
+/// ```rust
+/// use fitgirl_ecoqos::matcher::Target;
+///
+/// let target = Target::new("rz.exe")
+///     .with_executable_path("C:/Games/Setup/rz.exe")
+///     .with_parent_name("setup.tmp");
+/// assert_eq!(target.parent_name, Some("setup.tmp"));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Target<'a> {
+    pub name: &'a str,
+    pub executable_path: Option<&'a Path>,
+    pub command_line: Option<&'a str>,
+    pub parent_name: Option<&'a str>,
+}
+
+impl<'a> Target<'a> {
+    /// a target known by name only.
+    pub fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    /// name, executable path and command line of a process event.
+    pub fn from_event(event: &'a ProcessEvent) -> Self {
+        Self {
+            name: &event.name,
+            executable_path: event.executable_path.as_deref(),
+            command_line: event.command_line.as_deref(),
+            parent_name: None,
+        }
+    }
+
+    pub fn with_executable_path(mut self, path: &'a (impl AsRef<Path> + ?Sized)) -> Self {
+        self.executable_path = Some(path.as_ref());
+        self
+    }
+
+    pub fn with_command_line(mut self, command_line: &'a str) -> Self {
+        self.command_line = Some(command_line);
+        self
+    }
+
+    pub fn with_parent_name(mut self, parent_name: &'a str) -> Self {
+        self.parent_name = Some(parent_name);
+        self
+    }
+}
+
+/// Conditions of a rule besides its name patterns.
+#[derive(Debug, Clone)]
+struct Filters {
+    /// whether the rule lists any name or name regex.
+    by_name: bool,
+    paths: Option<GlobSet>,
+    #[cfg(feature = "regex")]
+    command_line: Option<RegexSet>,
+    /// parent names and name globs.
+    parents: Option<GlobSet>,
+}
+
+impl Filters {
+    fn new(rule: &Rule, normalizer: Normalizer) -> Result<Self, Error> {
+        #[cfg(feature = "regex")]
+        let by_name = !rule.names.is_empty() || !rule.regex.is_empty();
+        #[cfg(not(feature = "regex"))]
+        let by_name = !rule.names.is_empty();
+
+        let paths = if rule.paths.is_empty() {
+            None
+        } else {
+            let mut builder = GlobSetBuilder::new();
+            for path in &rule.paths {
+                builder.add(glob(path)?);
+            }
+            Some(builder.build()?)
+        };
+
+        let parents = if rule.parents.is_empty() {
+            None
+        } else {
+            let mut builder = GlobSetBuilder::new();
+            for name in &rule.parents {
+                builder.add(name_glob(name, normalizer)?);
+            }
+            Some(builder.build()?)
+        };
+
+        #[cfg(feature = "regex")]
+        let command_line = if rule.command_line_regex.is_empty() {
+            None
+        } else {
+            Some(RegexSet::new(&rule.command_line_regex)?)
+        };
+
+        Ok(Self {
+            by_name,
+            paths,
+            #[cfg(feature = "regex")]
+            command_line,
+            parents,
+        })
+    }
+
+    /// whether the rule has any condition at all, empty rules match nothing.
+    fn is_empty(&self) -> bool {
+        #[cfg(feature = "regex")]
+        let command_line = self.command_line.is_some();
+        #[cfg(not(feature = "regex"))]
+        let command_line = false;
+
+        !self.by_name && self.paths.is_none() && !command_line && self.parents.is_none()
+    }
+
+    fn matches(&self, target: &Target, normalizer: Normalizer) -> bool {
+        if let Some(paths) = &self.paths {
+            match target.executable_path {
+                Some(path) if paths.is_match(path) => {}
+                _ => return false,
+            }
+        }
+
+        #[cfg(feature = "regex")]
+        if let Some(command_line) = &self.command_line {
+            match target.command_line {
+                Some(line) if command_line.is_match(line) => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(parents) = &self.parents {
+            let mut buf = [0; NAME_BUF_LEN];
+            let parent = target
+                .parent_name
+                .map(|name| normalizer.normalize_in(name, &mut buf));
+            match parent {
+                Some(parent) if parents.is_match(&*parent) => {}
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+/// Globs match whole paths, `*` may cross separators, case-insensitive on Windows.
+pub(crate) fn glob(pattern: &str) -> Result<globset::Glob, Error> {
+    // `\` is no escape character on Windows, accept it as a separator
+    #[cfg(windows)]
+    let pattern = &pattern.replace('\\', "/");
+
+    Ok(GlobBuilder::new(pattern)
+        .case_insensitive(cfg!(windows))
+        .build()?)
+}
+
+/// Name globs match the whole normalized name, with `*` and `?` as the only wildcards.
+///
+/// Globs left with wildcards only once `.exe` is stripped, such as `*.exe` or `?*.exe`,
+/// match about every name and are rejected.
+pub(crate) fn name_glob(pattern: &str, normalizer: Normalizer) -> Result<globset::Glob, Error> {
+    let normalized = normalizer.normalize(pattern);
+    let catch_all =
+        |pattern: &str| pattern.contains('*') && pattern.chars().all(|c| matches!(c, '*' | '?'));
+    if catch_all(&normalized) && !catch_all(pattern) {
+        return Err(Error::CatchAllGlob(pattern.to_string()));
+    }
+
+    let mut escaped = String::with_capacity(normalized.len());
+    for c in normalized.chars() {
+        if matches!(c, '[' | ']' | '{' | '}' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    Ok(GlobBuilder::new(&escaped)
+        .case_insensitive(!normalizer.case_sensitive)
+        .backslash_escape(true)
+        .build()?)
+}
+
+/// Thread globs match the whole thread name, `.exe` has no meaning there.
+pub(crate) fn thread_glob(pattern: &str, case_sensitive: bool) -> Result<globset::Glob, Error> {
+    Ok(GlobBuilder::new(pattern)
+        .case_insensitive(!case_sensitive)
+        .build()?)
+}
+
+impl Matcher {
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let normalizer = Normalizer::new(config);
+        let blacklist = Rule {
+            names: config.blacklist.clone(),
+            #[cfg(feature = "regex")]
+            regex: config.blacklist_regex.clone(),
+            action: Action::Eco,
+            ..Default::default()
+        };
+        let rules = config.rules.iter().chain([&blacklist]);
+
+        let mut names = AHashMap::<_, Vec<_>>::new();
+        let mut name_globs = GlobSetBuilder::new();
+        let mut glob_rules = vec![];
+        #[cfg(feature = "regex")]
+        let mut patterns = vec![];
+        #[cfg(feature = "regex")]
+        let mut regex_rules = vec![];
+        let mut filters = vec![];
+        let mut matches = vec![];
+
+        for (index, rule) in rules.enumerate() {
+            for name in &rule.names {
+                if is_glob(name) {
+                    name_globs.add(name_glob(name, normalizer)?);
+                    glob_rules.push(index);
+                } else {
+                    let name = normalizer.normalize(name).into_owned();
+                    names.entry(name).or_default().push(index);
+                }
+            }
+            #[cfg(feature = "regex")]
+            for pattern in &rule.regex {
+                patterns.push(pattern.as_str());
+                regex_rules.push(index);
+            }
+            filters.push(Filters::new(rule, normalizer)?);
+            let threads = match rule.threads.is_empty() {
+                true => None,
+                false => Some(ThreadNames::new(&rule.threads, config.case_sensitive)?),
+            };
+            matches.push(RuleMatch {
+                action: rule.action,
+                inherit_depth: rule.inherit_depth.unwrap_or(config.inherit_depth),
+                threads,
+            });
+        }
+
+        Ok(Self {
+            normalizer,
+            names,
+            name_globs: name_globs.build()?,
+            glob_rules,
+            #[cfg(feature = "regex")]
+            regexset: RegexSetBuilder::new(patterns)
+                .case_insensitive(!config.case_sensitive)
+                .build()?,
+            #[cfg(feature = "regex")]
+            regex_rules,
+            filters,
+            rules: matches,
+        })
+    }
+
+    /// find the action of the first rule matching a process name.
+    pub fn find(&self, name: &str) -> Option<Action> {
+        self.find_rule(&Target::new(name)).map(|rule| rule.action)
+    }
+
+    /// find the first rule matching a target.
+    ///
+    /// Called for every new process, doesn't allocate for ASCII names
+    /// and up to 256 rules.
+    pub fn find_rule(&self, target: &Target) -> Option<RuleMatch> {
+        let mut buf = [0; NAME_BUF_LEN];
+        let name = self.normalizer.normalize_in(target.name, &mut buf);
+        let mut by_name = RuleBits::new(self.rules.len());
+        for &index in self.names.get(name.as_ref()).into_iter().flatten() {
+            by_name.insert(index);
+        }
+        if !self.name_globs.is_empty() {
+            GLOB_MATCHES.with_borrow_mut(|patterns| {
+                let candidate = Candidate::new(name.as_ref());
+                self.name_globs.matches_candidate_into(&candidate, patterns);
+                for &pattern in patterns.iter() {
+                    by_name.insert(self.glob_rules[pattern]);
+                }
+            });
+        }
+        #[cfg(feature = "regex")]
+        if !self.regexset.is_empty() {
+            REGEX_MATCHES.with_borrow_mut(|matched| {
+                matched.clear();
+                matched.resize(self.regexset.len(), false);
+                self.regexset.matches_read_at(matched, target.name, 0);
+                for (pattern, _) in matched.iter().enumerate().filter(|(_, m)| **m) {
+                    by_name.insert(self.regex_rules[pattern]);
+                }
+            });
+        }
+
+        self.filters
+            .iter()
+            .enumerate()
+            .position(|(index, filters)| {
+                !filters.is_empty()
+                    && (by_name.contains(index) || !filters.by_name)
+                    && filters.matches(target, self.normalizer)
+            })
+            .map(|index| self.rules[index].clone())
+    }
+
+    /// how names are compared.
+    pub fn normalizer(&self) -> Normalizer {
+        self.normalizer
+    }
+
+    /// check whether any rule matches a process name, including [`Action::Ignore`] ones.
+    pub fn is_match(&self, name: &str) -> bool {
+        self.find(name).is_some()
+    }
+}
+
+impl TryFrom<&Config> for Matcher {
+    type Error = Error;
+
+    fn try_from(config: &Config) -> Result<Self, Self::Error> {
+        Self::new(config)
+    }
+}
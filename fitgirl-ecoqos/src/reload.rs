@@ -0,0 +1,173 @@
+use std::{fmt, time::Duration};
+
+use futures_lite::{Stream, stream};
+use tracing::{error, info, warn};
+
+use crate::{Error, config::Config, loader::Loader, matcher::Matcher};
+
+/// Entries added and removed by an edit of the config.
+///
+/// Rules, blacklist entries and top-level options are compared
+/// as a whole, a changed rule shows up as removed and added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RulesDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl RulesDiff {
+    pub fn new(old: &Config, new: &Config) -> Self {
+        let mut removed = entries(old);
+        let mut added = vec![];
+        for entry in entries(new) {
+            match removed.iter().position(|old| old == &entry) {
+                Some(index) => {
+                    removed.remove(index);
+                }
+                None => added.push(entry),
+            }
+        }
+        Self { added, removed }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl fmt::Display for RulesDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let removed = self.removed.iter().map(|entry| ('-', entry));
+        let added = self.added.iter().map(|entry| ('+', entry));
+        for (index, (sign, entry)) in removed.chain(added).enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{sign} {entry}")?;
+        }
+        Ok(())
+    }
+}
+
+fn entries(config: &Config) -> Vec<String> {
+    let rules = config.rules.iter().map(|rule| format!("rule: {rule}"));
+    let blacklist = config
+        .blacklist
+        .iter()
+        .map(|name| format!("blacklist: {name:?}"));
+    #[cfg(feature = "regex")]
+    let blacklist = blacklist.chain(
+        config
+            .blacklist_regex
+            .iter()
+            .map(|pattern| format!("blacklist_regex: {pattern:?}")),
+    );
+    let options = [
+        format!("case_sensitive: {}", config.case_sensitive),
+        format!("ignore_exe_suffix: {}", config.ignore_exe_suffix),
+        format!("inherit_depth: {}", config.inherit_depth),
+    ];
+
+    rules.chain(blacklist).chain(options).collect()
+}
+
+/// A valid edit of the config.
+#[derive(Debug)]
+pub struct Reload {
+    /// rules compiled from the new config.
+    pub matcher: Matcher,
+    pub diff: RulesDiff,
+}
+
+/// Watches the config files of a [`Loader`] for edits.
+///
+/// Files are compared by content, so editors replacing them
+/// instead of writing in place are fine. A removed file is
+/// skipped, as on startup.
+#[derive(Debug)]
+pub struct Reloader {
+    loader: Loader,
+    /// last contents seen, valid or not.
+    contents: Vec<Option<String>>,
+    /// last failure to read, not to report it again.
+    read_error: Option<String>,
+    config: Config,
+}
+
+impl Reloader {
+    /// `config` is the one currently in use, loaded by `loader`.
+    pub fn new(loader: Loader, config: Config) -> Self {
+        let contents = loader.read().unwrap_or_default();
+        Self {
+            loader,
+            contents,
+            read_error: None,
+            config,
+        }
+    }
+
+    pub fn loader(&self) -> &Loader {
+        &self.loader
+    }
+
+    /// the last valid config.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// check the files once, returns the new rules if they were changed into a valid config.
+    ///
+    /// Invalid contents are reported only once, the current config is kept.
+    pub fn reload(&mut self) -> Result<Option<Reload>, Error> {
+        let contents = match self.loader.read() {
+            Ok(contents) => contents,
+            Err(e) if self.read_error == Some(e.to_string()) => return Ok(None),
+            Err(e) => {
+                self.read_error = Some(e.to_string());
+                return Err(e);
+            }
+        };
+        self.read_error = None;
+        if contents == self.contents {
+            return Ok(None);
+        }
+        self.contents = contents;
+
+        let config = self.loader.merge(&self.contents)?.config;
+        let matcher = Matcher::new(&config)?;
+
+        if config.linux != self.config.linux {
+            warn!("changes to [linux] take effect after a restart");
+        }
+        if config.windows != self.config.windows {
+            warn!("changes to [windows] take effect after a restart");
+        }
+        let diff = RulesDiff::new(&self.config, &config);
+        self.config = config;
+
+        Ok(Some(Reload { matcher, diff }))
+    }
+
+    /// check the files every `interval`, yields the rules of valid edits.
+    ///
+    /// Errors are logged and the stream goes on.
+    pub fn watch(self, interval: Duration) -> impl Stream<Item = Matcher> {
+        stream::unfold(self, move |mut reloader| async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match reloader.reload() {
+                    Ok(Some(Reload { matcher, diff })) => {
+                        if diff.is_empty() {
+                            info!("reloaded config, rules unchanged");
+                        } else {
+                            info!("reloaded config:\n{diff}");
+                        }
+                        return Some((matcher, reloader));
+                    }
+                    Ok(None) => {}
+                    Err(e) => error!("failed to reload config: {e}, keeping current rules"),
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,199 @@
+use listen_new_proc::StopHandle;
+use tracing::{error, info};
+
+use crate::Error;
+
+/// Service control manager glue, install and uninstall.
+#[cfg(windows)]
+pub mod windows;
+
+/// Name of the installed service.
+pub const SERVICE_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// State of the service, as reported to the service control manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceState {
+    StartPending,
+    Running,
+    StopPending,
+    Stopped,
+}
+
+impl ServiceState {
+    /// whether the service may go from this state to `next`.
+    ///
+    /// A service may stop from any state, e.g. when it fails to start.
+    pub fn can_become(self, next: Self) -> bool {
+        use ServiceState::*;
+
+        matches!(
+            (self, next),
+            (Stopped, StartPending)
+                | (StartPending, Running)
+                | (Running, StopPending)
+                | (StartPending | Running | StopPending, Stopped)
+        )
+    }
+}
+
+/// Status reported to the service control manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServiceStatus {
+    pub state: ServiceState,
+    /// non-zero if the service stopped on an error.
+    pub exit_code: u32,
+}
+
+impl ServiceStatus {
+    /// whether stop requests are accepted in this status.
+    pub fn accepts_stop(&self) -> bool {
+        self.state == ServiceState::Running
+    }
+}
+
+/// Requests from the service control manager, the ones the service cares about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceControl {
+    Stop,
+    /// the system is shutting down.
+    Shutdown,
+    /// report the current status again.
+    Interrogate,
+    Other,
+}
+
+/// Whether a [`ServiceControl`] was handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlResponse {
+    Handled,
+    NotImplemented,
+}
+
+/// Where the status of the service goes, the service control manager on Windows.
+pub trait StatusReporter {
+    fn report(&mut self, status: ServiceStatus) -> Result<(), Error>;
+}
+
+/// State machine of the service, platform independent.
+///
+/// The service starts, runs until a stop is requested, then stops:
+///
+/// ```rust
+/// use fitgirl_ecoqos::service::{ServiceControl, ServiceMachine, ServiceState, ServiceStatus};
+/// use listen_new_proc::StopHandle;
+///
+/// let mut reported = vec![];
+/// let stop = StopHandle::new();
+/// let mut machine = ServiceMachine::new(
+///     |status: ServiceStatus| {
+///         reported.push(status.state);
+///         Ok(())
+///     },
+///     stop.clone(),
+/// );
+///
+/// machine.start().unwrap();
+/// machine.started().unwrap();
+/// machine.control(ServiceControl::Stop);
+/// assert!(stop.is_stopped());
+/// machine.stopped(&Ok(())).unwrap();
+///
+/// drop(machine);
+/// assert_eq!(
+///     reported,
+///     [
+///         ServiceState::StartPending,
+///         ServiceState::Running,
+///         ServiceState::StopPending,
+///         ServiceState::Stopped,
+///     ]
+/// );
+/// ```
+#[derive(Debug)]
+pub struct ServiceMachine<R> {
+    reporter: R,
+    status: ServiceStatus,
+    /// stops the daemon on stop requests.
+    stop: StopHandle,
+}
+
+impl<F: FnMut(ServiceStatus) -> Result<(), Error>> StatusReporter for F {
+    fn report(&mut self, status: ServiceStatus) -> Result<(), Error> {
+        self(status)
+    }
+}
+
+impl<R: StatusReporter> ServiceMachine<R> {
+    pub fn new(reporter: R, stop: StopHandle) -> Self {
+        Self {
+            reporter,
+            status: ServiceStatus {
+                state: ServiceState::Stopped,
+                exit_code: 0,
+            },
+            stop,
+        }
+    }
+
+    pub fn status(&self) -> ServiceStatus {
+        self.status
+    }
+
+    pub fn reporter(&self) -> &R {
+        &self.reporter
+    }
+
+    fn transition(&mut self, state: ServiceState, exit_code: u32) -> Result<(), Error> {
+        if !self.status.state.can_become(state) {
+            return Err(Error::ServiceTransition(self.status.state, state));
+        }
+        self.status = ServiceStatus { state, exit_code };
+        self.reporter.report(self.status)
+    }
+
+    /// the service was launched, and is loading its config.
+    pub fn start(&mut self) -> Result<(), Error> {
+        self.transition(ServiceState::StartPending, 0)
+    }
+
+    /// the daemon is running, stop requests are accepted from now on.
+    pub fn started(&mut self) -> Result<(), Error> {
+        self.transition(ServiceState::Running, 0)
+    }
+
+    /// handle a request from the service control manager.
+    ///
+    /// Stop and shutdown requests stop the daemon, see [`ServiceMachine::stopped`]
+    /// for when it's done. Repeated ones are ignored.
+    pub fn control(&mut self, control: ServiceControl) -> ControlResponse {
+        let result = match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                if self.status.state == ServiceState::Running {
+                    info!("service stop requested: {control:?}");
+                    self.stop.stop();
+                    self.transition(ServiceState::StopPending, 0)
+                } else {
+                    Ok(())
+                }
+            }
+            ServiceControl::Interrogate => self.reporter.report(self.status),
+            ServiceControl::Other => return ControlResponse::NotImplemented,
+        };
+        if let Err(e) = result {
+            error!("failed to report service status: {e}");
+        }
+        ControlResponse::Handled
+    }
+
+    /// the daemon has stopped, with `result`.
+    pub fn stopped(&mut self, result: &Result<(), Error>) -> Result<(), Error> {
+        let exit_code = match result {
+            Ok(()) => 0,
+            Err(e) => {
+                error!("service failed: {e}");
+                1
+            }
+        };
+        self.transition(ServiceState::Stopped, exit_code)
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    ffi::{OsStr, OsString},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use listen_new_proc::StopHandle;
+use tracing::info;
+use windows_service::{
+    service::{
+        self, ServiceAccess, ServiceControlAccept, ServiceDependency, ServiceErrorControl,
+        ServiceExitCode, ServiceInfo, ServiceStartType, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+
+use super::{
+    ControlResponse, SERVICE_NAME, ServiceControl, ServiceMachine, ServiceState, ServiceStatus,
+    StatusReporter,
+};
+use crate::Error;
+
+/// Reports to the service control manager.
+#[derive(Debug, Clone, Copy)]
+pub struct ScmReporter(ServiceStatusHandle);
+
+impl StatusReporter for ScmReporter {
+    fn report(&mut self, status: ServiceStatus) -> Result<(), Error> {
+        let current_state = match status.state {
+            ServiceState::StartPending => service::ServiceState::StartPending,
+            ServiceState::Running => service::ServiceState::Running,
+            ServiceState::StopPending => service::ServiceState::StopPending,
+            ServiceState::Stopped => service::ServiceState::Stopped,
+        };
+        let controls_accepted = match status.accepts_stop() {
+            true => ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+            false => ServiceControlAccept::empty(),
+        };
+        let exit_code = match status.exit_code {
+            0 => ServiceExitCode::NO_ERROR,
+            code => ServiceExitCode::ServiceSpecific(code),
+        };
+        let wait_hint = match status.state {
+            ServiceState::StartPending | ServiceState::StopPending => Duration::from_secs(10),
+            _ => Duration::ZERO,
+        };
+
+        self.0.set_service_status(service::ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state,
+            controls_accepted,
+            exit_code,
+            checkpoint: 0,
+            wait_hint,
+            process_id: None,
+        })?;
+        Ok(())
+    }
+}
+
+impl From<service::ServiceControl> for ServiceControl {
+    fn from(control: service::ServiceControl) -> Self {
+        match control {
+            service::ServiceControl::Stop => Self::Stop,
+            service::ServiceControl::Shutdown => Self::Shutdown,
+            service::ServiceControl::Interrogate => Self::Interrogate,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl From<ControlResponse> for ServiceControlHandlerResult {
+    fn from(response: ControlResponse) -> Self {
+        match response {
+            ControlResponse::Handled => Self::NoError,
+            ControlResponse::NotImplemented => Self::NotImplemented,
+        }
+    }
+}
+
+/// Reports the service running, see [`run`].
+pub type Ready = Box<dyn FnOnce() -> Result<(), Error>>;
+
+/// run `daemon` as the service, from the `service_main` registered with
+/// `windows_service::service_dispatcher::start`.
+///
+/// The service is start pending until `daemon` calls [`Ready`], once its config
+/// is loaded. `daemon` must return once the [`StopHandle`] is stopped.
+pub fn run(daemon: impl FnOnce(StopHandle, Ready) -> Result<(), Error>) -> Result<(), Error> {
+    let stop = StopHandle::new();
+    // set once registered, controls may arrive before
+    let machine = Arc::new(Mutex::new(None::<ServiceMachine<ScmReporter>>));
+
+    let handler = {
+        let machine = machine.clone();
+        move |control: service::ServiceControl| match machine.lock().unwrap().as_mut() {
+            Some(machine) => machine.control(control.into()).into(),
+            None => ServiceControlHandlerResult::NotImplemented,
+        }
+    };
+    let handle = service_control_handler::register(SERVICE_NAME, handler)?;
+
+    let mut service = ServiceMachine::new(ScmReporter(handle), stop.clone());
+    service.start()?;
+    *machine.lock().unwrap() = Some(service);
+
+    let ready: Ready = {
+        let machine = machine.clone();
+        Box::new(move || {
+            let mut machine = machine.lock().unwrap();
+            machine.as_mut().expect("set above").started()
+        })
+    };
+    let result = daemon(stop, ready);
+
+    let mut machine = machine.lock().unwrap();
+    let service = machine.as_mut().expect("set above");
+    service.stopped(&result)?;
+    result
+}
+
+/// install the service, started with the system, and start it now.
+///
+/// It runs `executable` with `service`, and `--config` if given.
+pub fn install(executable: &Path, config: Option<&Path>) -> Result<(), Error> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )?;
+
+    let mut launch_arguments = vec![OsString::from("service")];
+    if let Some(config) = config {
+        launch_arguments.push("--config".into());
+        launch_arguments.push(std::path::absolute(config)?.into());
+    }
+    let info = ServiceInfo {
+        name: SERVICE_NAME.into(),
+        display_name: SERVICE_NAME.into(),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: executable.to_path_buf(),
+        launch_arguments,
+        // process events come from WMI
+        dependencies: vec![ServiceDependency::Service("Winmgmt".into())],
+        // LocalSystem
+        account_name: None,
+        account_password: None,
+    };
+    let service =
+        manager.create_service(&info, ServiceAccess::CHANGE_CONFIG | ServiceAccess::START)?;
+    service.set_description(env!("CARGO_PKG_DESCRIPTION"))?;
+    info!("installed service {SERVICE_NAME}");
+
+    service.start(&[] as &[&OsStr])?;
+    info!("started service {SERVICE_NAME}");
+    Ok(())
+}
+
+/// stop the service if running, and uninstall it.
+pub fn uninstall() -> Result<(), Error> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(
+        SERVICE_NAME,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    )?;
+
+    if service.query_status()?.current_state != service::ServiceState::Stopped {
+        service.stop()?;
+        info!("stopping service {SERVICE_NAME}");
+    }
+    // removed once stopped and every handle is closed
+    service.delete()?;
+    info!("uninstalled service {SERVICE_NAME}");
+    Ok(())
+}
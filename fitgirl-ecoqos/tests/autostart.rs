@@ -0,0 +1,65 @@
+use std::{fs, path::Path};
+
+use fitgirl_ecoqos::autostart::{LogonTask, RunLevel};
+
+/// compare with `tests/snapshots/<name>`, rewritten instead with `UPDATE_SNAPSHOTS=1`.
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(name);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap().replace("\r\n", "\n");
+    assert_eq!(actual, expected, "snapshot {name} differs");
+}
+
+#[test]
+fn daemon_task_xml() {
+    let task = LogonTask::daemon(
+        r"DESKTOP-1\alice",
+        Path::new(r"C:\Program Files\fitgirl-ecoqos\fitgirl-ecoqos.exe"),
+        Some(Path::new(r"D:\Games & Tools\config.toml")),
+    );
+
+    assert_snapshot("daemon_task.xml", &task.to_xml());
+}
+
+#[test]
+fn task_without_arguments() {
+    let task = LogonTask::new(r"PC\bob", r"C:\fitgirl-ecoqos.exe")
+        .with_run_level(RunLevel::LeastPrivilege);
+    let xml = task.to_xml();
+
+    assert!(!xml.contains("<Arguments>"));
+    assert!(xml.contains("<RunLevel>LeastPrivilege</RunLevel>"));
+}
+
+#[test]
+fn arguments_are_quoted_for_command_line_to_argv() {
+    let task = LogonTask::new(r"PC\bob", r"C:\fitgirl-ecoqos.exe").with_arguments([
+        "plain",
+        "",
+        r"C:\with space\",
+        r#"say "hi""#,
+        r"back\slash",
+    ]);
+
+    assert!(task.to_xml().contains(
+        r#"<Arguments>plain &quot;&quot; &quot;C:\with space\\&quot; &quot;say \&quot;hi\&quot;&quot; back\slash</Arguments>"#
+    ));
+}
+
+#[test]
+fn utf16_has_bom() {
+    let task = LogonTask::new(r"PC\bob", r"C:\fitgirl-ecoqos.exe");
+    let bytes = task.to_utf16();
+
+    assert_eq!(&bytes[..4], [0xff, 0xfe, b'<', 0]);
+    let units: Vec<_> = bytes
+        .chunks(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    assert_eq!(String::from_utf16(&units[1..]).unwrap(), task.to_xml());
+}
@@ -0,0 +1,255 @@
+use fitgirl_ecoqos::check::{Diagnostic, Location, Severity, check, has_errors};
+
+fn at(diagnostic: &Diagnostic) -> (usize, usize) {
+    let Location { line, column } = diagnostic.location.unwrap();
+    (line, column)
+}
+
+#[test]
+fn default_config_is_clean() {
+    let source = toml::to_string(&fitgirl_ecoqos::config::Config::default()).unwrap();
+
+    assert_eq!(check(&source), []);
+}
+
+#[test]
+fn syntax_error_is_located() {
+    let diagnostics = check("blacklist = [\"xtool.exe\"\n\n[[rule]]\n");
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(at(&diagnostics[0]).0, 3);
+    assert!(has_errors(&diagnostics));
+}
+
+#[test]
+fn type_errors_are_located() {
+    let diagnostics = check(
+        r#"
+[[rule]]
+names = ["rz.exe"]
+action = "turbo"
+
+[[rule]]
+names = ["7z.exe"]
+inherit_depth = -1
+"#,
+    );
+
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(at(&diagnostics[0]), (4, 10));
+    assert_eq!(diagnostics[0].rule, Some(0));
+    assert!(diagnostics[0].message.contains("turbo"));
+    assert_eq!(at(&diagnostics[1]), (8, 17));
+    assert_eq!(diagnostics[1].rule, Some(1));
+}
+
+#[test]
+fn unknown_keys_are_warned() {
+    let diagnostics = check(
+        r#"blacklist = []
+colour = "red"
+
+[[rule]]
+names = ["rz.exe"]
+acton = "high"
+
+[linux]
+nicer = 10
+"#,
+    );
+
+    let warnings: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.severity, at(d), d.rule, d.message.as_str()))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (
+                Severity::Warning,
+                (2, 1),
+                None,
+                "unknown key `colour`, ignored"
+            ),
+            (
+                Severity::Warning,
+                (6, 1),
+                Some(0),
+                "unknown key `acton`, ignored"
+            ),
+            (
+                Severity::Warning,
+                (9, 1),
+                None,
+                "unknown key `linux.nicer`, ignored"
+            ),
+        ]
+    );
+    assert!(!has_errors(&diagnostics));
+}
+
+#[test]
+fn invalid_globs_point_at_the_entry() {
+    let diagnostics = check(
+        r#"
+[[rule]]
+names = ["7z.exe"]
+
+[[rule]]
+paths = ["/games/**", "/opt/[x"]
+"#,
+    );
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].rule, Some(1));
+    assert_eq!(at(&diagnostics[0]), (6, 23));
+    assert!(
+        diagnostics[0]
+            .to_string()
+            .starts_with("6:23: error: rule #1: in `paths`: invalid glob")
+    );
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn invalid_regexes_point_at_the_entry() {
+    let diagnostics = check(
+        r#"blacklist_regex = ['re-.*\.exe', 're-(']
+
+[[rule]]
+regex = ['^ok$']
+command_line_regex = ['[']
+"#,
+    );
+
+    let found: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.severity, at(d), d.rule))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (Severity::Error, (1, 34), None),
+            (Severity::Error, (5, 23), Some(0)),
+        ]
+    );
+}
+
+#[test]
+fn duplicate_names_are_warned() {
+    let diagnostics = check(
+        r#"blacklist = ["xtool.exe", "XTool.exe", "7z.exe"]
+
+[[rule]]
+names = ["rz.exe", "rz.exe"]
+
+[[rule]]
+# conditional, does not shadow later rules
+names = ["7z.exe"]
+parents = ["setup.tmp"]
+
+[[rule]]
+names = ["7z.exe"]
+inline = true
+"#,
+    );
+
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (at(d), d.message.as_str()))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ((1, 27), "`XTool.exe`, same as `xtool.exe`, is listed twice"),
+            (
+                (1, 40),
+                "`7z.exe` is already matched by rule #2, it never matches here"
+            ),
+            ((4, 20), "`rz.exe` is listed twice"),
+            ((13, 1), "unknown key `inline`, ignored"),
+        ]
+    );
+}
+
+#[test]
+fn duplicates_follow_name_options() {
+    let diagnostics = check(
+        r#"case_sensitive = true
+ignore_exe_suffix = true
+blacklist = ["xtool.exe", "XTool.exe", "xtool"]
+"#,
+    );
+
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (at(d), d.message.as_str()))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (
+                (3, 27),
+                "`XTool.exe` differs from `xtool.exe` in blacklist only by case, names are case-sensitive"
+            ),
+            ((3, 40), "`xtool`, same as `xtool.exe`, is listed twice"),
+        ]
+    );
+}
+
+#[test]
+fn invalid_name_globs_are_located() {
+    let diagnostics = check("ignore_exe_suffix = true\nblacklist = [\"*.exe\"]\n");
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(at(&diagnostics[0]), (2, 14));
+    assert!(
+        diagnostics[0]
+            .message
+            .starts_with("in `blacklist`: \"*.exe\" matches every name")
+    );
+}
+
+#[test]
+fn rules_without_conditions_are_warned() {
+    let diagnostics = check("rule = [{ action = \"high\" }]\n");
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].rule, Some(0));
+    assert_eq!(at(&diagnostics[0]), (1, 9));
+    assert_eq!(diagnostics[0].message, "no condition, matches nothing");
+}
+
+#[test]
+fn invalid_thread_glob_is_located() {
+    let diagnostics = check(
+        r#"
+[[rule]]
+names = ["unarc.exe"]
+threads = ["worker-*", "io-[x"]
+"#,
+    );
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].rule, Some(0));
+    assert_eq!(at(&diagnostics[0]), (4, 24));
+    assert!(
+        diagnostics[0]
+            .to_string()
+            .starts_with("4:24: error: rule #0: in `threads`: invalid glob")
+    );
+}
+
+#[test]
+fn windows_table_keys() {
+    let diagnostics = check("[windows]\nignore_timer_resolution = true\ntimer = 1\n");
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(at(&diagnostics[0]), (3, 1));
+    assert_eq!(
+        diagnostics[0].message,
+        "unknown key `windows.timer`, ignored"
+    );
+}
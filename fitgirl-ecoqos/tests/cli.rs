@@ -0,0 +1,189 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use fitgirl_ecoqos::{
+    Error,
+    backend::{
+        QosController,
+        fake::{FakeController, FakeSource},
+    },
+    cli::{self, Cli, Command, ProcessTarget},
+    config::{Action, Config},
+    matcher::{Matcher, Normalizer},
+};
+use listen_new_proc::ProcessEvent;
+
+fn parse(args: &[&str]) -> Cli {
+    Cli::try_parse_from([&["fitgirl-ecoqos"], args].concat()).unwrap()
+}
+
+fn source() -> FakeSource {
+    FakeSource::default().with_running([
+        ProcessEvent::created(10, 1, "setup.tmp"),
+        ProcessEvent::created(11, 10, "unpack.exe"),
+        ProcessEvent::created(12, 1, "xtool.exe"),
+        ProcessEvent::created(13, 1, "xtool.exe"),
+        ProcessEvent::created(14, 1, "explorer.exe"),
+    ])
+}
+
+fn output(run: impl FnOnce(&mut Vec<u8>) -> Result<(), Error>) -> String {
+    let mut out = vec![];
+    run(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn run_is_the_default_command() {
+    let cli = parse(&[]);
+
+    assert_eq!(cli.command(), Command::Run);
+    assert_eq!(cli.config, None);
+}
+
+#[test]
+fn parse_subcommands() {
+    let cli = parse(&["apply", "1234", "--config", "/etc/fitgirl.toml"]);
+    assert_eq!(
+        cli.command(),
+        Command::Apply {
+            target: ProcessTarget::Pid(1234),
+            action: Action::Eco
+        }
+    );
+    assert_eq!(cli.config, Some(PathBuf::from("/etc/fitgirl.toml")));
+
+    let cli = parse(&["--config", "a.toml", "apply", "7z.exe", "--action", "high"]);
+    assert_eq!(
+        cli.command(),
+        Command::Apply {
+            target: ProcessTarget::Name("7z.exe".to_string()),
+            action: Action::High
+        }
+    );
+
+    assert_eq!(
+        parse(&["status"]).command(),
+        Command::Status { target: None }
+    );
+    assert_eq!(
+        parse(&["print-default-config"]).command(),
+        Command::PrintDefaultConfig
+    );
+    assert!(Cli::try_parse_from(["fitgirl-ecoqos", "restore"]).is_err());
+    assert!(Cli::try_parse_from(["fitgirl-ecoqos", "apply", "1", "--action", "turbo"]).is_err());
+    assert!(Cli::try_parse_from(["fitgirl-ecoqos", "apply", "1", "--action", "ignore"]).is_err());
+}
+
+#[test]
+fn apply_by_name_applies_to_every_process() {
+    let controller = FakeController::default();
+    let mut source = source();
+
+    let out = output(|out| {
+        // names are compared as rules compare them, ignoring case by default
+        let target = "XTool.exe".parse().unwrap();
+        let normalizer = Normalizer::default();
+        cli::apply(
+            out,
+            &mut source,
+            &controller,
+            normalizer,
+            &target,
+            Action::High,
+        )
+    });
+
+    assert_eq!(controller.applied(), [(12, Some(false)), (13, Some(false))]);
+    assert_eq!(out, "     12  xtool.exe: high\n     13  xtool.exe: high\n");
+
+    let target = "XTool.exe".parse().unwrap();
+    let normalizer = Normalizer {
+        case_sensitive: true,
+        ..Default::default()
+    };
+    let result = cli::apply(
+        &mut vec![],
+        &mut source,
+        &controller,
+        normalizer,
+        &target,
+        Action::High,
+    );
+    assert!(matches!(result, Err(Error::NoProcess(_))));
+}
+
+#[test]
+fn apply_fails_without_matching_process() {
+    let controller = FakeController::default();
+
+    let result = cli::apply(
+        &mut vec![],
+        &mut source(),
+        &controller,
+        Normalizer::default(),
+        &ProcessTarget::Pid(99),
+        Action::Restore,
+    );
+
+    assert!(matches!(result, Err(Error::NoProcess(target)) if target == "PID 99"));
+    assert_eq!(controller.applied(), []);
+}
+
+#[test]
+fn list_shows_planned_actions() {
+    let config: Config = toml::from_str(
+        r#"
+        blacklist = ["xtool.exe"]
+
+        [[rule]]
+        names = ["setup.tmp"]
+        action = "high"
+        inherit_depth = 1
+        "#,
+    )
+    .unwrap();
+    let matcher = Matcher::new(&config).unwrap();
+
+    let out = output(|out| cli::list(out, &mut source(), &matcher));
+
+    assert_eq!(
+        out,
+        [
+            "    PID     PPID  ACTION   NAME",
+            "     10        1  high     setup.tmp",
+            "     11       10  high     unpack.exe",
+            "     12        1  eco      xtool.exe",
+            "     13        1  eco      xtool.exe",
+            "",
+        ]
+        .join("\n")
+    );
+}
+
+#[test]
+fn status_queries_controller() {
+    let controller = FakeController::default();
+    controller.apply(12, Some(true)).unwrap();
+    let matcher = Matcher::new(&Config::default()).unwrap();
+
+    let out = output(|out| cli::status(out, &mut source(), &controller, &matcher, None));
+    assert_eq!(
+        out,
+        [
+            "    PID  THROTTLED NAME",
+            "     10  no        setup.tmp",
+            "     12  yes       xtool.exe",
+            "     13  no        xtool.exe",
+            "",
+        ]
+        .join("\n")
+    );
+
+    let target = ProcessTarget::Name("explorer.exe".to_string());
+    let out = output(|out| cli::status(out, &mut source(), &controller, &matcher, Some(&target)));
+    assert_eq!(
+        out,
+        "    PID  THROTTLED NAME\n     14  no        explorer.exe\n"
+    );
+}
@@ -0,0 +1,592 @@
+use fitgirl_ecoqos::{
+    backend::{
+        QosController,
+        fake::{FakeController, FakeSource},
+    },
+    config::{Action, Config},
+    daemon::{Daemon, Restored, Summary, Tracked},
+    matcher::Matcher,
+};
+use listen_new_proc::{ProcessEvent, StopHandle};
+
+fn process(process_id: u32, name: &str) -> ProcessEvent {
+    ProcessEvent::created(process_id, 1, name)
+}
+
+fn child(process_id: u32, parent_process_id: u32, name: &str) -> ProcessEvent {
+    ProcessEvent::created(process_id, parent_process_id, name)
+}
+
+fn daemon(config: &Config) -> (Daemon<FakeController>, FakeController) {
+    let controller = FakeController::default();
+    let daemon = Daemon::new(Matcher::new(config).unwrap(), controller.clone());
+    (daemon, controller)
+}
+
+#[tokio::test]
+async fn throttles_blacklisted_processes() {
+    let (mut daemon, controller) = daemon(&Config::default());
+    let mut source = FakeSource::new([
+        process(10, "explorer.exe"),
+        process(11, "xtool.exe"),
+        process(12, "oo2reck.exe"),
+    ]);
+
+    daemon.run(&mut source).await.unwrap();
+
+    assert_eq!(controller.applied(), [(11, Some(true)), (12, Some(true))]);
+    assert!(controller.query(11).unwrap());
+    assert!(!controller.query(10).unwrap());
+}
+
+#[tokio::test]
+async fn applies_rule_actions() {
+    let config: Config = toml::from_str(
+        r#"
+        [[rule]]
+        names = ["7z.exe"]
+        action = "high"
+
+        [[rule]]
+        names = ["setup.tmp"]
+        action = "restore"
+
+        [[rule]]
+        names = ["rz.exe"]
+        action = "ignore"
+        "#,
+    )
+    .unwrap();
+    let (mut daemon, controller) = daemon(&config);
+    let mut source = FakeSource::new([
+        process(20, "7z.exe"),
+        process(21, "setup.tmp"),
+        process(22, "rz.exe"),
+    ]);
+
+    daemon.run(&mut source).await.unwrap();
+
+    assert_eq!(controller.applied(), [(20, Some(false)), (21, None)]);
+}
+
+#[test]
+fn handle_reports_matched_action() {
+    let (mut daemon, controller) = daemon(&Config::default());
+
+    assert_eq!(daemon.handle(&process(30, "xtool.exe")), Some(Action::Eco));
+    assert_eq!(daemon.handle(&process(31, "notepad.exe")), None);
+    assert_eq!(controller.applied(), [(30, Some(true))]);
+}
+
+#[test]
+fn exit_events_are_not_matched() {
+    let (mut daemon, controller) = daemon(&Config::default());
+
+    assert_eq!(
+        daemon.handle(&ProcessEvent::exited(30, 1, "xtool.exe")),
+        None
+    );
+    assert!(controller.applied().is_empty());
+}
+
+#[tokio::test]
+async fn forgets_exited_processes() {
+    let (mut daemon, _) = daemon(&Config::default());
+    let mut source = FakeSource::new([
+        process(40, "xtool.exe"),
+        process(41, "rz.exe"),
+        process(42, "notepad.exe"),
+        ProcessEvent::exited(40, 1, "xtool.exe"),
+        ProcessEvent::exited(42, 1, "notepad.exe"),
+    ]);
+
+    daemon.run(&mut source).await.unwrap();
+
+    let tracked: Vec<_> = daemon.tracked().iter().collect();
+    assert_eq!(
+        tracked,
+        [(
+            41,
+            &Tracked {
+                name: "rz.exe".to_string(),
+                action: Action::Eco,
+                inherit_depth: 0,
+            }
+        )]
+    );
+}
+
+#[test]
+fn sweep_applies_rules_to_running_processes() {
+    let config: Config = toml::from_str(
+        r#"
+        blacklist = ["xtool.exe", "rz.exe"]
+
+        [[rule]]
+        names = ["setup.tmp"]
+        action = "ignore"
+        "#,
+    )
+    .unwrap();
+    let (mut daemon, controller) = daemon(&config);
+
+    let summary = daemon.sweep(&[
+        process(50, "explorer.exe"),
+        process(51, "xtool.exe"),
+        process(52, "setup.tmp"),
+        process(53, "rz.exe"),
+    ]);
+
+    assert_eq!(
+        summary,
+        Summary {
+            scanned: 4,
+            matched: 3,
+            applied: 2,
+            failed: 0,
+        }
+    );
+    assert_eq!(
+        summary.to_string(),
+        "scanned 4 processes, 3 matched, 2 applied, 0 failed"
+    );
+    assert_eq!(controller.applied(), [(51, Some(true)), (53, Some(true))]);
+    assert!(daemon.tracked().contains(51));
+}
+
+#[tokio::test]
+async fn run_sweeps_before_handling_events() {
+    let (mut daemon, controller) = daemon(&Config::default());
+    let mut source = FakeSource::new([process(61, "rz.exe")])
+        .with_running([process(60, "xtool.exe"), process(62, "explorer.exe")]);
+
+    daemon.run(&mut source).await.unwrap();
+
+    assert_eq!(controller.applied(), [(60, Some(true)), (61, Some(true))]);
+}
+
+fn inherit_config() -> Config {
+    toml::from_str(
+        r#"
+        blacklist = ["xtool.exe"]
+
+        [[rule]]
+        names = ["setup.tmp"]
+        inherit_depth = 2
+
+        [[rule]]
+        names = ["7z.exe"]
+        action = "high"
+        "#,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn children_inherit_up_to_max_depth() {
+    let (mut daemon, controller) = daemon(&inherit_config());
+    let mut source = FakeSource::new([
+        child(70, 1, "setup.tmp"),
+        child(71, 70, "unpack-4f2a.exe"),
+        child(72, 71, "decompress.exe"),
+        child(73, 72, "too-deep.exe"),
+        // own rules still win over inherited ones
+        child(74, 70, "7z.exe"),
+        // blacklist does not inherit by default
+        child(75, 1, "xtool.exe"),
+        child(76, 75, "helper.exe"),
+    ]);
+
+    daemon.run(&mut source).await.unwrap();
+
+    assert_eq!(
+        controller.applied(),
+        [
+            (70, Some(true)),
+            (71, Some(true)),
+            (72, Some(true)),
+            (74, Some(false)),
+            (75, Some(true)),
+        ]
+    );
+    assert_eq!(daemon.tracked().get(71).unwrap().inherit_depth, 1);
+    assert_eq!(daemon.tracked().get(72).unwrap().inherit_depth, 0);
+}
+
+#[tokio::test]
+async fn exited_parent_is_not_inherited() {
+    let (mut daemon, controller) = daemon(&inherit_config());
+    let mut source = FakeSource::new([
+        child(80, 1, "setup.tmp"),
+        ProcessEvent::exited(80, 1, "setup.tmp"),
+        child(81, 80, "unpack.exe"),
+    ]);
+
+    daemon.run(&mut source).await.unwrap();
+
+    assert_eq!(controller.applied(), [(80, Some(true))]);
+}
+
+#[test]
+fn children_reported_before_parents_inherit() {
+    let (mut daemon, controller) = daemon(&inherit_config());
+
+    // netlink and `/proc` may report a child before its parent
+    daemon.handle(&child(122, 121, "decompress.exe"));
+    daemon.handle(&child(121, 120, "unpack.exe"));
+    assert!(controller.applied().is_empty());
+    daemon.handle(&child(120, 1, "setup.tmp"));
+    // the parent exited before being reported, the orphan is dropped
+    daemon.handle(&child(124, 123, "helper.exe"));
+    daemon.handle(&ProcessEvent::exited(123, 1, "setup.tmp"));
+    daemon.handle(&child(123, 1, "setup.tmp"));
+
+    assert_eq!(
+        controller.applied(),
+        [
+            (120, Some(true)),
+            (121, Some(true)),
+            (122, Some(true)),
+            (123, Some(true))
+        ]
+    );
+    assert_eq!(daemon.summary().matched, 4);
+}
+
+#[test]
+fn sweep_inherits_regardless_of_order() {
+    let mut config = inherit_config();
+    config.inherit_depth = 1;
+    let (mut daemon, controller) = daemon(&config);
+
+    let summary = daemon.sweep(&[
+        child(92, 91, "decompress.exe"),
+        child(91, 90, "unpack.exe"),
+        child(90, 1, "setup.tmp"),
+        child(94, 93, "helper.exe"),
+        child(93, 1, "xtool.exe"),
+    ]);
+
+    assert_eq!(summary.matched, 5);
+    let mut applied = controller.applied();
+    applied.sort();
+    assert_eq!(
+        applied,
+        [
+            (90, Some(true)),
+            (91, Some(true)),
+            (92, Some(true)),
+            (93, Some(true)),
+            (94, Some(true)),
+        ]
+    );
+}
+
+#[test]
+fn rules_match_parent_names() {
+    let config: Config = toml::from_str(
+        r#"
+        blacklist = []
+
+        [[rule]]
+        names = ["rz.exe"]
+        parents = ["setup.tmp"]
+        "#,
+    )
+    .unwrap();
+    let (mut daemon, controller) = daemon(&config);
+
+    // the parent was running before the daemon
+    daemon.sweep(&[child(100, 1, "setup.tmp")]);
+    daemon.handle(&child(101, 100, "rz.exe"));
+    daemon.handle(&child(102, 1, "rz.exe"));
+    // the PID was reused by another process
+    daemon.handle(&ProcessEvent::exited(100, 1, "setup.tmp"));
+    daemon.handle(&child(100, 1, "explorer.exe"));
+    daemon.handle(&child(103, 100, "rz.exe"));
+
+    // the parent is reported after its child
+    daemon.handle(&child(105, 104, "rz.exe"));
+    daemon.handle(&child(104, 1, "setup.tmp"));
+
+    assert_eq!(controller.applied(), [(101, Some(true)), (105, Some(true))]);
+}
+
+#[test]
+fn reload_applies_to_new_processes_only() {
+    let (mut daemon, controller) = daemon(&Config::default());
+    daemon.handle(&process(110, "xtool.exe"));
+
+    let config: Config = toml::from_str(r#"blacklist = ["7z.exe"]"#).unwrap();
+    daemon.reload(Matcher::new(&config).unwrap());
+    daemon.handle(&process(111, "xtool.exe"));
+    daemon.handle(&process(112, "7z.exe"));
+
+    assert_eq!(controller.applied(), [(110, Some(true)), (112, Some(true))]);
+    assert!(daemon.tracked().contains(110));
+}
+
+#[tokio::test]
+async fn run_with_reload_stops_when_events_end() {
+    let (mut daemon, controller) = daemon(&Config::default());
+    let mut source = FakeSource::new([process(120, "xtool.exe")]);
+
+    daemon
+        .run_with_reload(
+            &mut source,
+            futures_lite::stream::pending(),
+            &StopHandle::new(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(controller.applied(), [(120, Some(true))]);
+}
+
+#[test]
+fn restore_all_puts_back_prior_states() {
+    let config: Config = toml::from_str(
+        r#"
+        blacklist = ["xtool.exe"]
+
+        [[rule]]
+        names = ["7z.exe"]
+        action = "high"
+        "#,
+    )
+    .unwrap();
+    let (mut daemon, controller) = daemon(&config);
+    // throttled by hand before the daemon started
+    controller.apply(12, Some(true)).unwrap();
+
+    daemon.handle(&process(11, "xtool.exe"));
+    daemon.handle(&process(12, "7z.exe"));
+    daemon.handle(&process(13, "xtool.exe"));
+    daemon.handle(&ProcessEvent::exited(13, 1, "xtool.exe"));
+
+    assert_eq!(
+        daemon.restore_all(),
+        Restored {
+            restored: 2,
+            failed: 0
+        }
+    );
+    let states = controller.states();
+    assert_eq!(states[&11], None);
+    assert_eq!(states[&12], Some(true));
+    // exited, not restored
+    assert_eq!(states[&13], Some(true));
+    assert!(daemon.tracked().is_empty());
+    assert_eq!(daemon.restore_all(), Restored::default());
+}
+
+#[test]
+fn restore_all_keeps_state_from_before_first_action() {
+    let (mut daemon, controller) = daemon(&Config::default());
+
+    let event = process(11, "xtool.exe");
+    daemon.sweep(std::slice::from_ref(&event));
+    daemon.sweep(std::slice::from_ref(&event));
+    daemon.restore_all();
+
+    assert_eq!(
+        controller.applied(),
+        [(11, Some(true)), (11, Some(true)), (11, None)]
+    );
+}
+
+#[test]
+fn restore_all_puts_back_processes_which_exec() {
+    let (mut daemon, controller) = daemon(&Config::default());
+    controller.apply(12, Some(false)).unwrap();
+
+    daemon.handle(&process(12, "xtool.exe"));
+    // the throttled process replaced itself, netlink reports another creation
+    daemon.handle(&process(12, "worker.exe"));
+    assert!(!daemon.tracked().contains(12));
+
+    assert_eq!(daemon.restore_all().restored, 1);
+    assert_eq!(controller.states()[&12], Some(false));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn restore_all_puts_back_processes_which_exec_between_polls() {
+    use listen_new_proc::linux::procfs::diff;
+    use std::collections::HashMap;
+
+    let (mut daemon, controller) = daemon(&Config::default());
+    controller.apply(12, Some(false)).unwrap();
+    let previous = HashMap::from([(12, process(12, "xtool.exe"))]);
+    let current = HashMap::from([(12, process(12, "worker.exe"))]);
+
+    daemon.handle(&previous[&12]);
+    // polling /proc only sees another name under the same pid and parent
+    for event in diff(&previous, &current) {
+        daemon.handle(&event);
+    }
+    assert!(!daemon.tracked().contains(12));
+
+    assert_eq!(daemon.restore_all().restored, 1);
+    assert_eq!(controller.states()[&12], Some(false));
+}
+
+#[tokio::test]
+async fn stop_ends_run_after_pending_events() {
+    let (mut daemon, controller) = daemon(&Config::default());
+    let mut source = FakeSource::new([process(130, "xtool.exe"), process(131, "rz.exe")])
+        .with_running([process(129, "fsb.exe")])
+        .keep_open();
+    let stop = StopHandle::new();
+    stop.stop();
+
+    daemon
+        .run_with_reload(&mut source, futures_lite::stream::pending(), &stop)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        controller.applied(),
+        [(129, Some(true)), (130, Some(true)), (131, Some(true))]
+    );
+}
+
+#[tokio::test]
+async fn stop_from_another_task() {
+    let (mut daemon, _) = daemon(&Config::default());
+    let mut source = FakeSource::new([]).keep_open();
+    let stop = StopHandle::new();
+
+    tokio::spawn({
+        let stop = stop.clone();
+        async move { stop.stop() }
+    });
+    daemon
+        .run_with_reload(&mut source, futures_lite::stream::pending(), &stop)
+        .await
+        .unwrap();
+}
+
+#[test]
+fn summary_counts_sweeps_and_events() {
+    let (mut daemon, _) = daemon(
+        &toml::from_str(
+            r#"
+        blacklist = ["xtool.exe"]
+
+        [[rule]]
+        names = ["rz.exe"]
+        action = "ignore"
+        "#,
+        )
+        .unwrap(),
+    );
+
+    daemon.sweep(&[process(10, "xtool.exe"), process(11, "explorer.exe")]);
+    daemon.handle(&process(12, "rz.exe"));
+    daemon.handle(&process(13, "xtool.exe"));
+    daemon.handle(&ProcessEvent::exited(13, 1, "xtool.exe"));
+
+    assert_eq!(
+        daemon.summary(),
+        Summary {
+            scanned: 4,
+            matched: 3,
+            applied: 2,
+            failed: 0
+        }
+    );
+}
+
+fn thread_config() -> Config {
+    toml::from_str(
+        r#"
+        inherit_depth = 1
+
+        [[rule]]
+        names = ["unarc.exe"]
+        threads = ["worker-*"]
+        "#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn thread_rules_only_touch_named_threads() {
+    let (mut daemon, controller) = daemon(&thread_config());
+    controller.set_threads(20, [(200, "ui"), (201, "Worker-1"), (202, "worker-2")]);
+
+    assert_eq!(daemon.handle(&process(20, "unarc.exe")), Some(Action::Eco));
+
+    assert_eq!(controller.applied(), []);
+    assert_eq!(
+        controller.applied_threads(),
+        [(201, Some(true)), (202, Some(true))]
+    );
+    assert!(daemon.tracked().contains(20));
+}
+
+#[test]
+fn rescan_catches_threads_named_later() {
+    let (mut daemon, controller) = daemon(&thread_config());
+    controller.set_threads(20, [(200, "ui"), (201, "")]);
+    daemon.handle(&process(20, "unarc.exe"));
+    assert_eq!(controller.applied_threads(), []);
+
+    controller.set_threads(20, [(200, "ui"), (201, "worker-1"), (202, "worker-2")]);
+    assert_eq!(daemon.rescan_threads(), 2);
+    assert_eq!(daemon.rescan_threads(), 0);
+
+    daemon.handle(&ProcessEvent::exited(20, 1, "unarc.exe"));
+    controller.set_threads(20, [(203, "worker-3")]);
+    assert_eq!(daemon.rescan_threads(), 0);
+    assert_eq!(
+        controller.applied_threads(),
+        [(201, Some(true)), (202, Some(true))]
+    );
+}
+
+#[test]
+fn children_inherit_thread_names() {
+    let (mut daemon, controller) = daemon(&thread_config());
+    controller.set_threads(21, [(210, "ui"), (211, "worker-1")]);
+
+    daemon.handle(&process(20, "unarc.exe"));
+    daemon.handle(&child(21, 20, "helper.exe"));
+
+    assert_eq!(controller.applied(), []);
+    assert_eq!(controller.applied_threads(), [(211, Some(true))]);
+}
+
+#[test]
+fn restore_all_puts_back_thread_states() {
+    let (mut daemon, controller) = daemon(&thread_config());
+    controller.set_threads(20, [(200, "ui"), (201, "worker-1"), (202, "worker-2")]);
+    // set by the program itself
+    controller.apply_thread(20, 202, Some(false)).unwrap();
+    daemon.handle(&process(20, "unarc.exe"));
+
+    assert_eq!(daemon.restore_all().restored, 1);
+    let states = controller.thread_states();
+    assert_eq!(states[&201], None);
+    assert_eq!(states[&202], Some(false));
+    assert_eq!(controller.applied_threads().len(), 5);
+    assert_eq!(daemon.rescan_threads(), 0);
+}
+
+#[test]
+fn rescan_applies_to_reused_thread_ids() {
+    let (mut daemon, controller) = daemon(&thread_config());
+    controller.set_threads(20, [(200, "ui"), (201, "worker-1")]);
+    daemon.handle(&process(20, "unarc.exe"));
+
+    // the worker exited, and a thread reset by the program got its id
+    controller.set_threads(20, [(200, "ui")]);
+    assert_eq!(daemon.rescan_threads(), 0);
+    controller.apply_thread(20, 201, Some(false)).unwrap();
+    controller.set_threads(20, [(200, "ui"), (201, "worker-2")]);
+    assert_eq!(daemon.rescan_threads(), 1);
+
+    daemon.restore_all();
+    assert_eq!(controller.thread_states()[&201], Some(false));
+}
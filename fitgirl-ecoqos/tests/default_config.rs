@@ -0,0 +1,37 @@
+use std::{fs, path::PathBuf, process};
+
+use fitgirl_ecoqos::{check::check, config::Config};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("fitgirl-ecoqos-{}-{name}", process::id()))
+        .join("config.toml")
+}
+
+#[test]
+fn default_toml_parses_to_default() {
+    let toml = Config::default_toml();
+
+    assert_eq!(toml::from_str::<Config>(&toml).unwrap(), Config::default());
+    assert_eq!(check(&toml), []);
+    assert!(toml.contains("\"xtool.exe\""));
+    #[cfg(feature = "regex")]
+    assert!(toml.contains(r"'re-.*\.exe'"));
+    #[cfg(not(feature = "regex"))]
+    assert!(toml.contains(r"# blacklist_regex = ['re-.*\.exe']"));
+}
+
+#[test]
+fn write_default_never_overwrites() {
+    let path = temp_path("write-default");
+    let _ = fs::remove_file(&path);
+
+    assert!(Config::write_default(&path).unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), Config::default_toml());
+
+    fs::write(&path, "blacklist = []\n").unwrap();
+    assert!(!Config::write_default(&path).unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "blacklist = []\n");
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,238 @@
+#![cfg(target_os = "linux")]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Child, Command},
+};
+
+use fitgirl_ecoqos::{
+    backend::{
+        QosController,
+        linux::{LinuxController, cgroup_of, cgroup_root, ioprio_class, nice, scheduler},
+    },
+    config::{CgroupConfig, LinuxConfig},
+};
+
+struct Sleeper(Child);
+
+impl Sleeper {
+    fn spawn() -> Self {
+        Self(Command::new("sleep").arg("30").spawn().unwrap())
+    }
+
+    fn pid(&self) -> u32 {
+        self.0.id()
+    }
+}
+
+impl Drop for Sleeper {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn throttle_and_restore_child() {
+    let child = Sleeper::spawn();
+    let pid = child.pid();
+    let controller = LinuxController::new(LinuxConfig::default()).unwrap();
+
+    controller.apply(pid, Some(true)).unwrap();
+    assert_eq!(scheduler(pid).unwrap(), libc::SCHED_IDLE);
+    assert_eq!(nice(pid).unwrap(), 19);
+    assert_eq!(ioprio_class(pid).unwrap(), 3);
+    assert!(controller.query(pid).unwrap());
+
+    // restoring a higher priority requires CAP_SYS_NICE
+    if controller.apply(pid, None).is_err() {
+        return;
+    }
+    assert_eq!(scheduler(pid).unwrap(), libc::SCHED_OTHER);
+    assert_eq!(nice(pid).unwrap(), 0);
+    assert_eq!(ioprio_class(pid).unwrap(), 0);
+    assert!(!controller.query(pid).unwrap());
+}
+
+#[test]
+fn save_and_restore_child() {
+    let child = Sleeper::spawn();
+    let pid = child.pid();
+    let controller = LinuxController::new(LinuxConfig::default()).unwrap();
+
+    // nice 5, best-effort I/O at level 4
+    assert_eq!(unsafe { libc::setpriority(libc::PRIO_PROCESS, pid, 5) }, 0);
+    let ioprio = (2 << 13) | 4;
+    assert_eq!(
+        unsafe { libc::syscall(libc::SYS_ioprio_set, 1, pid, ioprio) },
+        0
+    );
+
+    let saved = controller.save(pid).unwrap();
+    controller.apply(pid, Some(true)).unwrap();
+    assert_eq!(nice(pid).unwrap(), 19);
+    assert_eq!(ioprio_class(pid).unwrap(), 3);
+
+    // raising priorities back requires CAP_SYS_NICE
+    match controller.restore(pid, &saved) {
+        Err(_) if unsafe { libc::geteuid() } != 0 => return,
+        result => result.unwrap(),
+    }
+    assert_eq!(scheduler(pid).unwrap(), libc::SCHED_OTHER);
+    assert_eq!(nice(pid).unwrap(), 5);
+    assert_eq!(
+        unsafe { libc::syscall(libc::SYS_ioprio_get, 1, pid) },
+        ioprio
+    );
+}
+
+#[test]
+fn partial_throttle_keeps_scheduler() {
+    let child = Sleeper::spawn();
+    let pid = child.pid();
+    let controller = LinuxController::new(LinuxConfig {
+        sched_idle: false,
+        nice: 10,
+        ioprio_idle: false,
+        cgroup: None,
+    })
+    .unwrap();
+
+    controller.apply(pid, Some(true)).unwrap();
+    assert_eq!(scheduler(pid).unwrap(), libc::SCHED_OTHER);
+    assert_eq!(nice(pid).unwrap(), 10);
+    assert_ne!(ioprio_class(pid).unwrap(), 3);
+    assert!(!controller.query(pid).unwrap());
+}
+
+#[test]
+fn exited_process_is_an_error() {
+    let mut child = Command::new("true").spawn().unwrap();
+    let pid = child.id();
+    child.wait().unwrap();
+
+    let controller = LinuxController::default();
+    assert!(controller.apply(pid, Some(true)).is_err());
+}
+
+/// the cgroup v2 mount, `None` if there is none.
+fn test_cgroup_root() -> Option<PathBuf> {
+    // /sys/fs/cgroup/unified on hybrid hierarchies
+    ["/sys/fs/cgroup/unified/x", "/sys/fs/cgroup/x"]
+        .into_iter()
+        .find_map(|path| cgroup_root(Path::new(path)).ok())
+}
+
+/// relative path of a cgroup created for a test, moving `pid` into it.
+fn enter_test_cgroup(root: &Path, name: &str, pid: u32) -> PathBuf {
+    let relative = PathBuf::from(name);
+    fs::create_dir_all(root.join(&relative)).unwrap();
+    fs::write(root.join(&relative).join("cgroup.procs"), pid.to_string()).unwrap();
+    relative
+}
+
+#[test]
+fn move_into_cgroup() {
+    // requires a writable cgroup v2 hierarchy
+    let Some(root) = test_cgroup_root() else {
+        return;
+    };
+    let name = format!("fitgirl-ecoqos-test-{}", std::process::id());
+    let path = root.join(&name);
+    let Ok(controller) = LinuxController::new(LinuxConfig {
+        cgroup: Some(CgroupConfig {
+            path: path.clone(),
+            cpu_weight: None,
+            cpu_max: None,
+        }),
+        ..Default::default()
+    }) else {
+        return;
+    };
+
+    // start from a non-root cgroup, next to ours
+    let child = Sleeper::spawn();
+    let pid = child.pid();
+    let origin = enter_test_cgroup(&root, &format!("{name}-origin"), pid);
+
+    controller.apply(pid, Some(true)).unwrap();
+    assert_eq!(cgroup_of(pid).unwrap(), Some(PathBuf::from(&name)));
+
+    controller.apply(pid, None).unwrap();
+    assert_eq!(cgroup_of(pid).unwrap(), Some(origin.clone()));
+
+    // a cgroup with the same name elsewhere is not ours
+    let other = Sleeper::spawn();
+    let nested = enter_test_cgroup(&root, &format!("{name}-origin/{name}"), other.pid());
+    controller.apply(other.pid(), None).unwrap();
+    assert_eq!(cgroup_of(other.pid()).unwrap(), Some(nested.clone()));
+
+    drop(child);
+    drop(other);
+    let _ = fs::remove_dir(root.join(nested));
+    let _ = fs::remove_dir(root.join(origin));
+    let _ = fs::remove_dir(&path);
+}
+
+#[test]
+fn restore_moves_back_to_saved_cgroup() {
+    let Some(root) = test_cgroup_root() else {
+        return;
+    };
+    let path = root.join(format!("fitgirl-ecoqos-restore-{}", std::process::id()));
+    let Ok(controller) = LinuxController::new(LinuxConfig {
+        cgroup: Some(CgroupConfig {
+            path: path.clone(),
+            cpu_weight: None,
+            cpu_max: None,
+        }),
+        ..Default::default()
+    }) else {
+        return;
+    };
+
+    let child = Sleeper::spawn();
+    let pid = child.pid();
+    let before = cgroup_of(pid).unwrap();
+
+    let saved = controller.save(pid).unwrap();
+    controller.apply(pid, Some(true)).unwrap();
+    assert_ne!(cgroup_of(pid).unwrap(), before);
+    controller.restore(pid, &saved).unwrap();
+    assert_eq!(cgroup_of(pid).unwrap(), before);
+
+    drop(child);
+    let _ = fs::remove_dir(&path);
+}
+
+#[test]
+fn query_only_leaves_cgroup_alone() {
+    let path = std::env::temp_dir().join(format!("fitgirl-ecoqos-query-{}", std::process::id()));
+    let child = Sleeper::spawn();
+    let controller = LinuxController::query_only(LinuxConfig {
+        cgroup: Some(CgroupConfig {
+            path: path.clone(),
+            cpu_weight: Some(1),
+            cpu_max: None,
+        }),
+        ..Default::default()
+    });
+
+    assert!(!controller.query(child.pid()).unwrap());
+    assert!(!path.exists());
+}
+
+#[test]
+fn threads_are_listed_by_name() {
+    let child = Sleeper::spawn();
+    let pid = child.pid();
+    let controller = LinuxController::new(LinuxConfig::default()).unwrap();
+
+    let threads = controller.threads(pid).unwrap();
+    assert_eq!(threads, [(pid, "sleep".to_string())]);
+
+    controller.apply_thread(pid, pid, Some(true)).unwrap();
+    assert_eq!(scheduler(pid).unwrap(), libc::SCHED_IDLE);
+    assert_eq!(nice(pid).unwrap(), 19);
+}
@@ -0,0 +1,182 @@
+use std::{fs, path::PathBuf, process};
+
+use fitgirl_ecoqos::{
+    Error,
+    config::{Action, Config},
+    loader::{Loader, Origin},
+};
+
+/// config files in a temp directory, removed on drop.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("fitgirl-ecoqos-{}-{name}", process::id()));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn file(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn defaults_only() {
+    let layered = Loader::new().load().unwrap();
+
+    assert_eq!(layered.config, Config::default());
+    assert_eq!(layered.origins["blacklist"], Origin::Default);
+    assert_eq!(layered.origins["linux.nice"], Origin::Default);
+}
+
+#[test]
+fn later_layers_win() {
+    let dir = TempDir::new("layers");
+    let system = dir.file(
+        "system.toml",
+        r#"
+        inherit_depth = 1
+        blacklist = ["xtool.exe"]
+
+        [linux]
+        nice = 10
+        sched_idle = false
+        "#,
+    );
+    let user = dir.file(
+        "user.toml",
+        r#"
+        blacklist = ["rz.exe"]
+
+        [linux]
+        nice = 15
+        "#,
+    );
+
+    let layered = Loader::new()
+        .with_file(&system)
+        .with_file(dir.0.join("missing.toml"))
+        .with_file(&user)
+        .load()
+        .unwrap();
+
+    let config = &layered.config;
+    // arrays are replaced, tables merged
+    assert_eq!(config.blacklist, ["rz.exe"]);
+    assert_eq!(config.inherit_depth, 1);
+    assert_eq!(config.linux.nice, 15);
+    assert!(!config.linux.sched_idle);
+    assert!(config.linux.ioprio_idle);
+
+    let origins = &layered.origins;
+    assert_eq!(origins["blacklist"], Origin::File(user.clone()));
+    assert_eq!(origins["inherit_depth"], Origin::File(system.clone()));
+    assert_eq!(origins["linux.nice"], Origin::File(user));
+    assert_eq!(origins["linux.sched_idle"], Origin::File(system));
+    assert_eq!(origins["linux.ioprio_idle"], Origin::Default);
+}
+
+#[test]
+fn env_overrides_files() {
+    let dir = TempDir::new("env");
+    let user = dir.file(
+        "user.toml",
+        r#"
+        inherit_depth = 1
+
+        [[rule]]
+        names = ["7z.exe"]
+        action = "high"
+        "#,
+    );
+
+    let layered = Loader::new()
+        .with_file(&user)
+        .with_env(env(&[
+            ("FITGIRL_ECOQOS_INHERIT_DEPTH", "3"),
+            ("FITGIRL_ECOQOS_BLACKLIST", r#"["rz.exe", "xtool.exe"]"#),
+            ("FITGIRL_ECOQOS_LINUX__NICE", "5"),
+            ("FITGIRL_ECOQOS_LINUX__CGROUP__PATH", "/sys/fs/cgroup/eco"),
+            ("PATH", "/usr/bin"),
+        ]))
+        .load()
+        .unwrap();
+
+    let config = &layered.config;
+    assert_eq!(config.inherit_depth, 3);
+    assert_eq!(config.blacklist, ["rz.exe", "xtool.exe"]);
+    assert_eq!(config.rules[0].action, Action::High);
+    assert_eq!(config.linux.nice, 5);
+    // a plain string, not valid TOML on its own
+    let cgroup = config.linux.cgroup.as_ref().unwrap();
+    assert_eq!(cgroup.path, PathBuf::from("/sys/fs/cgroup/eco"));
+
+    let origins = &layered.origins;
+    assert_eq!(
+        origins["inherit_depth"],
+        Origin::Env("FITGIRL_ECOQOS_INHERIT_DEPTH".to_string())
+    );
+    assert_eq!(origins["rule"], Origin::File(user));
+    assert_eq!(
+        origins["linux.cgroup.path"],
+        Origin::Env("FITGIRL_ECOQOS_LINUX__CGROUP__PATH".to_string())
+    );
+    // columns are as wide as the longest key
+    assert!(layered.origins_table().lines().any(|line| {
+        line.split_whitespace()
+            .eq(["linux.nice", "env", "FITGIRL_ECOQOS_LINUX__NICE"])
+    }));
+    assert_eq!(origins["windows.ignore_timer_resolution"], Origin::Default);
+}
+
+#[test]
+fn required_file_must_exist() {
+    let dir = TempDir::new("required");
+    let missing = dir.0.join("missing.toml");
+
+    let result = Loader::new().with_required_file(&missing).load();
+
+    assert!(matches!(result, Err(Error::ConfigFile(path, _)) if path == missing));
+}
+
+#[test]
+fn errors_name_the_source() {
+    let dir = TempDir::new("errors");
+    let broken = dir.file("broken.toml", "blacklist = [");
+
+    let error = Loader::new().with_file(&broken).load().unwrap_err();
+    assert!(error.to_string().starts_with(&*broken.to_string_lossy()));
+
+    let error = Loader::new()
+        .with_env(env(&[("FITGIRL_ECOQOS_LINUX__NICE", "very")]))
+        .load()
+        .unwrap_err();
+    assert!(matches!(error, Error::InvalidMerge(_)));
+    assert!(error.to_string().contains("env FITGIRL_ECOQOS_LINUX__NICE"));
+
+    // a plain string where an array is expected
+    let error = Loader::new()
+        .with_env(env(&[("FITGIRL_ECOQOS_BLACKLIST", "xtool.exe")]))
+        .load()
+        .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .starts_with("invalid merged config: blacklist (env FITGIRL_ECOQOS_BLACKLIST): ")
+    );
+}
@@ -0,0 +1,425 @@
+use fitgirl_ecoqos::{
+    config::{Action, Config, Rule},
+    matcher::{Matcher, Target},
+};
+
+fn config(
+    blacklist: &[&str],
+    #[cfg_attr(not(feature = "regex"), allow(unused_variables))] blacklist_regex: &[&str],
+) -> Config {
+    Config {
+        blacklist: blacklist.iter().map(|s| s.to_string()).collect(),
+        #[cfg(feature = "regex")]
+        blacklist_regex: blacklist_regex.iter().map(|s| s.to_string()).collect(),
+        rules: vec![],
+        ..Default::default()
+    }
+}
+
+#[cfg_attr(not(feature = "regex"), allow(clippy::needless_update))]
+fn rule(names: &[&str], action: Action) -> Rule {
+    Rule {
+        names: names.iter().map(|s| s.to_string()).collect(),
+        action,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn default_config_matches_both_lists() {
+    let matcher = Matcher::new(&Config::default()).unwrap();
+
+    assert!(matcher.is_match("xtool.exe"));
+    assert!(matcher.is_match("setup.tmp"));
+    #[cfg(feature = "regex")]
+    assert!(matcher.is_match("re-unpack.exe"));
+
+    assert!(!matcher.is_match("explorer.exe"));
+}
+
+#[test]
+fn exact_name_ignores_case_by_default() {
+    let matcher = Matcher::new(&config(&["xtool.exe"], &[])).unwrap();
+
+    assert!(matcher.is_match("xtool.exe"));
+    assert!(matcher.is_match("XTool.EXE"));
+    assert!(!matcher.is_match("xtool.exe.bak"));
+    assert!(!matcher.is_match("xtool"));
+}
+
+#[test]
+fn case_sensitive_names() {
+    let config = Config {
+        case_sensitive: true,
+        ..config(&["xtool.exe", "cls-*.exe"], &[])
+    };
+    let matcher = Matcher::new(&config).unwrap();
+
+    assert!(matcher.is_match("xtool.exe"));
+    assert!(!matcher.is_match("XTool.exe"));
+    assert!(matcher.is_match("cls-srep.exe"));
+    assert!(!matcher.is_match("CLS-srep.exe"));
+}
+
+#[test]
+fn name_globs() {
+    let matcher = Matcher::new(&config(&["cls-magic2*_x??.exe"], &[])).unwrap();
+
+    assert!(matcher.is_match("cls-magic2_x64.exe"));
+    assert!(matcher.is_match("CLS-Magic2l_x86.exe"));
+    assert!(!matcher.is_match("cls-magic2_x64.dll"));
+    assert!(!matcher.is_match("cls-magic2_x640.exe"));
+}
+
+#[test]
+fn ignore_exe_suffix() {
+    let config = Config {
+        ignore_exe_suffix: true,
+        rules: vec![Rule {
+            parents: vec!["setup".to_string()],
+            ..rule(&["oo2reck"], Action::High)
+        }],
+        ..config(&["xtool.exe", "rz", "fsb*"], &[])
+    };
+    let matcher = Matcher::new(&config).unwrap();
+
+    assert!(matcher.is_match("xtool"));
+    assert!(matcher.is_match("XTool.exe"));
+    assert!(matcher.is_match("rz.EXE"));
+    assert!(matcher.is_match("fsb.exe"));
+    assert!(!matcher.is_match("rz.tmp"));
+
+    let child = Target::new("oo2reck.exe").with_parent_name("Setup.exe");
+    assert_eq!(matcher.find_rule(&child).unwrap().action, Action::High);
+}
+
+#[test]
+fn suffix_is_kept_by_default() {
+    let matcher = Matcher::new(&config(&["rz"], &[])).unwrap();
+
+    assert!(matcher.is_match("rz"));
+    assert!(!matcher.is_match("rz.exe"));
+}
+
+#[test]
+fn empty_config_matches_nothing() {
+    let matcher = Matcher::new(&config(&[], &[])).unwrap();
+
+    assert!(!matcher.is_match("xtool.exe"));
+    assert!(!matcher.is_match(""));
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn union_of_name_and_regex() {
+    let matcher = Matcher::new(&config(&["xtool.exe"], &[r"^re-.*\.exe$"])).unwrap();
+
+    // only in blacklist
+    assert!(matcher.is_match("xtool.exe"));
+    // only matched by regex
+    assert!(matcher.is_match("re-pack.exe"));
+    // neither
+    assert!(!matcher.is_match("re-pack.dll"));
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_follows_case_sensitivity() {
+    let matcher = Matcher::new(&config(&[], &[r"^re-.*\.exe$"])).unwrap();
+    assert!(matcher.is_match("RE-pack.EXE"));
+
+    let config = Config {
+        case_sensitive: true,
+        ..config(&[], &[r"^re-.*\.exe$"])
+    };
+    let matcher = Matcher::new(&config).unwrap();
+    assert!(!matcher.is_match("RE-pack.EXE"));
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn invalid_regex_is_rejected() {
+    assert!(Matcher::new(&config(&[], &["re-(.exe"])).is_err());
+}
+
+#[test]
+fn blacklist_defaults_to_eco() {
+    let matcher = Matcher::new(&config(&["xtool.exe"], &[])).unwrap();
+
+    assert_eq!(matcher.find("xtool.exe"), Some(Action::Eco));
+    assert_eq!(matcher.find("explorer.exe"), None);
+}
+
+#[test]
+fn first_matching_rule_wins() {
+    let mut config = config(&["xtool.exe", "rz.exe"], &[]);
+    config.rules = vec![
+        rule(&["xtool.exe"], Action::High),
+        rule(&["xtool.exe", "7z.exe"], Action::Restore),
+        rule(&["rz.exe"], Action::Ignore),
+    ];
+    let matcher = Matcher::new(&config).unwrap();
+
+    assert_eq!(matcher.find("xtool.exe"), Some(Action::High));
+    assert_eq!(matcher.find("7z.exe"), Some(Action::Restore));
+    // ignore rules still count as a match, and shadow the blacklist
+    assert_eq!(matcher.find("rz.exe"), Some(Action::Ignore));
+    assert!(matcher.is_match("rz.exe"));
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn earlier_regex_rule_beats_later_name_rule() {
+    let mut config = config(&[], &[]);
+    config.rules = vec![
+        Rule {
+            regex: vec![r"^x.*\.exe$".to_string()],
+            action: Action::High,
+            ..Default::default()
+        },
+        rule(&["xtool.exe"], Action::Ignore),
+    ];
+    let matcher = Matcher::new(&config).unwrap();
+
+    assert_eq!(matcher.find("xtool.exe"), Some(Action::High));
+}
+
+#[test]
+fn action_maps_to_efficiency_mode() {
+    assert_eq!(Action::Eco.efficiency_mode(), Some(Some(true)));
+    assert_eq!(Action::High.efficiency_mode(), Some(Some(false)));
+    assert_eq!(Action::Restore.efficiency_mode(), Some(None));
+    assert_eq!(Action::Ignore.efficiency_mode(), None);
+}
+
+#[test]
+fn parse_rule_tables() {
+    let config: Config = toml::from_str(
+        r#"
+        blacklist = ["xtool.exe"]
+
+        [[rule]]
+        names = ["7z.exe"]
+        action = "high"
+
+        [[rule]]
+        names = ["setup.tmp"]
+        action = "default"
+
+        [[rule]]
+        names = ["rz.exe"]
+        "#,
+    )
+    .unwrap();
+    let actions: Vec<_> = config.rules.iter().map(|r| r.action).collect();
+    assert_eq!(actions, [Action::High, Action::Restore, Action::Eco]);
+
+    let matcher = Matcher::new(&config).unwrap();
+    assert_eq!(matcher.find("7z.exe"), Some(Action::High));
+    assert_eq!(matcher.find("xtool.exe"), Some(Action::Eco));
+}
+
+fn scoped_config() -> Config {
+    toml::from_str(
+        r#"
+        blacklist = []
+
+        [[rule]]
+        names = ["rz.exe"]
+        paths = ["/games/installers/**"]
+        parents = ["setup.tmp"]
+
+        [[rule]]
+        paths = ["/opt/*/bin/unarc"]
+        action = "high"
+
+        [[rule]]
+        # no condition at all, matches nothing
+        action = "restore"
+        "#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn path_and_parent_narrow_down_names() {
+    let matcher = Matcher::new(&scoped_config()).unwrap();
+    let target = Target::new("rz.exe")
+        .with_executable_path("/games/installers/elden/rz.exe")
+        .with_parent_name("setup.tmp");
+
+    assert_eq!(matcher.find_rule(&target).unwrap().action, Action::Eco);
+    // each condition must hold
+    assert_eq!(
+        matcher.find_rule(&target.with_executable_path("/usr/bin/rz.exe")),
+        None
+    );
+    assert_eq!(
+        matcher.find_rule(&target.with_parent_name("explorer.exe")),
+        None
+    );
+    // an unknown path or parent does not match either
+    assert_eq!(matcher.find("rz.exe"), None);
+}
+
+#[test]
+fn parents_may_be_globs() {
+    let config: Config = toml::from_str(
+        r#"
+        blacklist = []
+
+        [[rule]]
+        names = ["rz.exe"]
+        parents = ["setup*.tmp", "setup[1].exe"]
+        "#,
+    )
+    .unwrap();
+    let matcher = Matcher::new(&config).unwrap();
+    let child = Target::new("rz.exe");
+
+    assert!(
+        matcher
+            .find_rule(&child.with_parent_name("Setup1.tmp"))
+            .is_some()
+    );
+    assert!(
+        matcher
+            .find_rule(&child.with_parent_name("setup[1].exe"))
+            .is_some()
+    );
+    assert!(
+        matcher
+            .find_rule(&child.with_parent_name("setup1.exe"))
+            .is_none()
+    );
+}
+
+#[test]
+fn rules_without_names_match_any_name() {
+    let matcher = Matcher::new(&scoped_config()).unwrap();
+    let target = Target::new("unarc").with_executable_path("/opt/unarc-1.2/bin/unarc");
+
+    assert_eq!(matcher.find_rule(&target).unwrap().action, Action::High);
+    assert_eq!(matcher.find("anything"), None);
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn command_line_regex() {
+    let config: Config = toml::from_str(
+        r#"
+        [[rule]]
+        regex = ['^7z']
+        command_line_regex = ['\s-mmt\d+', '\bx\b']
+        "#,
+    )
+    .unwrap();
+    let matcher = Matcher::new(&config).unwrap();
+
+    let extract = Target::new("7z.exe").with_command_line("7z.exe x archive.7z");
+    assert_eq!(matcher.find_rule(&extract).unwrap().action, Action::Eco);
+    let list = Target::new("7z.exe").with_command_line("7z.exe l archive.7z");
+    assert_eq!(matcher.find_rule(&list), None);
+}
+
+#[test]
+fn invalid_glob_is_rejected() {
+    let config: Config = toml::from_str(
+        r#"
+        [[rule]]
+        paths = ["/games/[installers"]
+        "#,
+    )
+    .unwrap();
+
+    assert!(Matcher::new(&config).is_err());
+}
+
+#[test]
+fn brackets_in_names_are_literal() {
+    let matcher = Matcher::new(&config(
+        &["setup[1].exe", "cls-[magic*.exe", "{x}.exe"],
+        &[],
+    ))
+    .unwrap();
+
+    assert!(matcher.is_match("setup[1].exe"));
+    assert!(!matcher.is_match("setup1.exe"));
+    assert!(matcher.is_match("cls-[magic2.exe"));
+    assert!(matcher.is_match("{X}.exe"));
+    assert!(!matcher.is_match("x.exe"));
+}
+
+#[test]
+fn catch_all_name_glob_is_rejected() {
+    let catch_all = Config {
+        ignore_exe_suffix: true,
+        ..config(&["*.exe"], &[])
+    };
+    assert!(Matcher::new(&catch_all).is_err());
+    let catch_all = Config {
+        ignore_exe_suffix: true,
+        ..config(&["?*.exe"], &[])
+    };
+    assert!(Matcher::new(&catch_all).is_err());
+
+    let explicit = Config {
+        ignore_exe_suffix: true,
+        ..config(&["*_x64.exe", "*"], &[])
+    };
+    let matcher = Matcher::new(&explicit).unwrap();
+    assert!(matcher.is_match("setup_x64"));
+    // an explicit `*` still matches everything
+    assert!(matcher.is_match("bash"));
+}
+
+#[test]
+fn non_ascii_and_long_names() {
+    let long = format!("{}.exe", "a".repeat(300));
+    let matcher = Matcher::new(&config(&["ÄRGER.exe", "A*.EXE"], &[])).unwrap();
+
+    assert!(matcher.is_match("ärger.EXE"));
+    assert!(matcher.is_match(&long));
+    assert!(matcher.is_match(&long.to_uppercase()));
+}
+
+#[test]
+fn many_rules() {
+    let mut config = config(&[], &[]);
+    config.rules = (0..300)
+        .map(|i| rule(&[&format!("tool{i}.exe")], Action::High))
+        .collect();
+    config.rules.push(rule(&["tool*.exe"], Action::Ignore));
+    let matcher = Matcher::new(&config).unwrap();
+
+    assert_eq!(matcher.find("tool299.exe"), Some(Action::High));
+    assert_eq!(matcher.find("tool300.exe"), Some(Action::Ignore));
+    assert_eq!(matcher.find("tool.dll"), None);
+}
+
+#[test]
+fn rules_carry_thread_names() {
+    let config: Config = toml::from_str(
+        r#"
+        [[rule]]
+        names = ["unarc.exe"]
+        threads = ["worker-*", "io"]
+        "#,
+    )
+    .unwrap();
+    let matcher = Matcher::new(&config).unwrap();
+
+    let threads = matcher
+        .find_rule(&Target::new("unarc.exe"))
+        .unwrap()
+        .threads
+        .unwrap();
+    assert_eq!(threads.patterns(), ["worker-*", "io"]);
+    assert!(threads.is_match("Worker-1"));
+    assert!(threads.is_match("IO"));
+    assert!(!threads.is_match("io-2"));
+    assert_eq!(
+        matcher.find_rule(&Target::new("rz.exe")).unwrap().threads,
+        None
+    );
+}
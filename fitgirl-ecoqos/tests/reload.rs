@@ -0,0 +1,166 @@
+use std::{fs, path::PathBuf, process, time::Duration};
+
+use fitgirl_ecoqos::{
+    config::{Action, Config},
+    loader::Loader,
+    reload::{Reload, Reloader, RulesDiff},
+};
+use futures_lite::StreamExt;
+
+/// config file in the temp directory, removed on drop.
+struct TempConfig(PathBuf);
+
+impl TempConfig {
+    fn new(name: &str, contents: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("fitgirl-ecoqos-{}-{name}.toml", process::id()));
+        fs::write(&path, contents).unwrap();
+        Self(path)
+    }
+
+    fn write(&self, contents: &str) {
+        fs::write(&self.0, contents).unwrap();
+    }
+}
+
+impl Drop for TempConfig {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+const INITIAL: &str = r#"
+blacklist = ["xtool.exe"]
+
+[[rule]]
+names = ["7z.exe"]
+action = "high"
+"#;
+
+fn reloader(file: &TempConfig) -> Reloader {
+    let loader = Loader::new().with_file(&file.0);
+    let config = loader.load().unwrap().config;
+    Reloader::new(loader, config)
+}
+
+#[test]
+fn unchanged_file_is_not_reloaded() {
+    let file = TempConfig::new("unchanged", INITIAL);
+    let mut reloader = reloader(&file);
+
+    assert!(reloader.reload().unwrap().is_none());
+    file.write(INITIAL);
+    assert!(reloader.reload().unwrap().is_none());
+}
+
+#[test]
+fn edit_swaps_rules_and_reports_diff() {
+    let file = TempConfig::new("edit", INITIAL);
+    let mut reloader = reloader(&file);
+
+    file.write(
+        r#"
+        blacklist = ["xtool.exe", "rz.exe"]
+
+        [[rule]]
+        names = ["7z.exe"]
+        action = "ignore"
+        "#,
+    );
+    let Reload { matcher, diff } = reloader.reload().unwrap().unwrap();
+
+    assert_eq!(matcher.find("rz.exe"), Some(Action::Eco));
+    assert_eq!(matcher.find("7z.exe"), Some(Action::Ignore));
+    assert_eq!(
+        diff,
+        RulesDiff {
+            added: vec![
+                r#"rule: names=["7z.exe"] action=ignore"#.to_string(),
+                r#"blacklist: "rz.exe""#.to_string(),
+            ],
+            removed: vec![r#"rule: names=["7z.exe"] action=high"#.to_string()],
+        }
+    );
+    assert_eq!(
+        diff.to_string(),
+        [
+            r#"- rule: names=["7z.exe"] action=high"#,
+            r#"+ rule: names=["7z.exe"] action=ignore"#,
+            r#"+ blacklist: "rz.exe""#,
+        ]
+        .join("\n")
+    );
+    assert_eq!(reloader.config().blacklist, ["xtool.exe", "rz.exe"]);
+}
+
+#[test]
+fn invalid_edit_keeps_current_config() {
+    let file = TempConfig::new("invalid", INITIAL);
+    let mut reloader = reloader(&file);
+    let before = reloader.config().clone();
+
+    file.write("blacklist = [\"xtool.exe\"");
+    assert!(reloader.reload().is_err());
+    // reported once
+    assert!(reloader.reload().unwrap().is_none());
+    assert_eq!(reloader.config(), &before);
+
+    file.write(
+        r#"
+        [[rule]]
+        paths = ["[unclosed"]
+        "#,
+    );
+    assert!(reloader.reload().is_err());
+    assert_eq!(reloader.config(), &before);
+
+    file.write(INITIAL);
+    let reload = reloader.reload().unwrap().unwrap();
+    assert!(reload.diff.is_empty());
+}
+
+#[test]
+fn removed_file_falls_back_to_default() {
+    let file = TempConfig::new("removed", INITIAL);
+    let mut reloader = reloader(&file);
+
+    fs::remove_file(&file.0).unwrap();
+    let Reload { matcher, .. } = reloader.reload().unwrap().unwrap();
+
+    assert_eq!(reloader.config(), &Config::default());
+    assert_eq!(matcher.find("7z.exe"), None);
+    assert_eq!(matcher.find("setup.tmp"), Some(Action::Eco));
+}
+
+#[test]
+fn missing_required_file_is_reported_once() {
+    let file = TempConfig::new("required", INITIAL);
+    let loader = Loader::new().with_required_file(&file.0);
+    let config = loader.load().unwrap().config;
+    let mut reloader = Reloader::new(loader, config);
+
+    fs::remove_file(&file.0).unwrap();
+    assert!(reloader.reload().is_err());
+    assert!(reloader.reload().unwrap().is_none());
+    assert_eq!(reloader.config().blacklist, ["xtool.exe"]);
+
+    file.write(r#"blacklist = ["rz.exe"]"#);
+    assert!(reloader.reload().unwrap().is_some());
+}
+
+#[tokio::test]
+async fn watch_yields_valid_edits() {
+    let file = TempConfig::new("watch", INITIAL);
+    let mut updates = Box::pin(reloader(&file).watch(Duration::from_millis(10)));
+
+    file.write("blacklist = [");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    file.write(r#"blacklist = ["rz.exe"]"#);
+
+    let matcher = tokio::time::timeout(Duration::from_secs(5), updates.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(matcher.find("rz.exe"), Some(Action::Eco));
+    assert_eq!(matcher.find("xtool.exe"), None);
+}
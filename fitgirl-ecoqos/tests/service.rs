@@ -0,0 +1,148 @@
+use fitgirl_ecoqos::{
+    Error,
+    service::{
+        ControlResponse, ServiceControl, ServiceMachine, ServiceState, ServiceStatus,
+        StatusReporter,
+    },
+};
+use listen_new_proc::StopHandle;
+
+/// Records every reported status.
+#[derive(Debug, Default)]
+struct Recorder {
+    reported: Vec<ServiceStatus>,
+}
+
+impl StatusReporter for Recorder {
+    fn report(&mut self, status: ServiceStatus) -> Result<(), Error> {
+        self.reported.push(status);
+        Ok(())
+    }
+}
+
+fn machine() -> (ServiceMachine<Recorder>, StopHandle) {
+    let stop = StopHandle::new();
+    (ServiceMachine::new(Recorder::default(), stop.clone()), stop)
+}
+
+fn states(machine: &ServiceMachine<Recorder>) -> Vec<ServiceState> {
+    machine
+        .reporter()
+        .reported
+        .iter()
+        .map(|s| s.state)
+        .collect()
+}
+
+#[test]
+fn stop_request_stops_daemon() {
+    let (mut machine, stop) = machine();
+    machine.start().unwrap();
+    assert!(!machine.status().accepts_stop());
+    machine.started().unwrap();
+    assert!(machine.status().accepts_stop());
+
+    assert_eq!(
+        machine.control(ServiceControl::Stop),
+        ControlResponse::Handled
+    );
+    assert!(stop.is_stopped());
+    assert!(!machine.status().accepts_stop());
+    machine.stopped(&Ok(())).unwrap();
+
+    assert_eq!(
+        states(&machine),
+        [
+            ServiceState::StartPending,
+            ServiceState::Running,
+            ServiceState::StopPending,
+            ServiceState::Stopped,
+        ]
+    );
+    assert_eq!(machine.status().exit_code, 0);
+}
+
+#[test]
+fn shutdown_is_a_stop_and_repeats_are_ignored() {
+    let (mut machine, stop) = machine();
+    machine.start().unwrap();
+    machine.started().unwrap();
+
+    machine.control(ServiceControl::Shutdown);
+    machine.control(ServiceControl::Stop);
+
+    assert!(stop.is_stopped());
+    assert_eq!(machine.reporter().reported.len(), 3);
+}
+
+#[test]
+fn stop_before_running_is_ignored() {
+    let (mut machine, stop) = machine();
+    machine.start().unwrap();
+
+    assert_eq!(
+        machine.control(ServiceControl::Stop),
+        ControlResponse::Handled
+    );
+    assert!(!stop.is_stopped());
+    assert_eq!(machine.status().state, ServiceState::StartPending);
+}
+
+#[test]
+fn interrogate_reports_again_and_others_are_not_implemented() {
+    let (mut machine, _) = machine();
+    machine.start().unwrap();
+    machine.started().unwrap();
+
+    assert_eq!(
+        machine.control(ServiceControl::Interrogate),
+        ControlResponse::Handled
+    );
+    assert_eq!(
+        machine.control(ServiceControl::Other),
+        ControlResponse::NotImplemented
+    );
+    assert_eq!(
+        states(&machine),
+        [
+            ServiceState::StartPending,
+            ServiceState::Running,
+            ServiceState::Running
+        ]
+    );
+}
+
+#[test]
+fn failure_is_reported_in_exit_code() {
+    let (mut machine, _) = machine();
+    machine.start().unwrap();
+
+    let result = Err(Error::NoProcess("x".to_string()));
+    machine.stopped(&result).unwrap();
+
+    assert_eq!(
+        machine.status(),
+        ServiceStatus {
+            state: ServiceState::Stopped,
+            exit_code: 1
+        }
+    );
+}
+
+#[test]
+fn invalid_transitions_are_rejected() {
+    let (mut machine, _) = machine();
+
+    assert!(matches!(
+        machine.started(),
+        Err(Error::ServiceTransition(
+            ServiceState::Stopped,
+            ServiceState::Running
+        ))
+    ));
+    assert!(machine.stopped(&Ok(())).is_err());
+    assert!(machine.reporter().reported.is_empty());
+
+    machine.start().unwrap();
+    assert!(machine.start().is_err());
+}
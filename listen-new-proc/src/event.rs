@@ -0,0 +1,85 @@
+use std::{path::PathBuf, time::SystemTime};
+
+/// What happened to a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Created,
+    Exited,
+}
+
+/// Platform independent process lifecycle event.
+///
+/// ```rust
+/// use listen_new_proc::{EventKind, ProcessEvent};
+///
+/// let event = ProcessEvent::created(42, 4, "xtool.exe").with_command_line("xtool.exe -d");
+/// assert_eq!(event.kind, EventKind::Created);
+/// assert_eq!(event.command_line.as_deref(), Some("xtool.exe -d"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProcessEvent {
+    pub kind: EventKind,
+    pub process_id: u32,
+    pub parent_process_id: u32,
+    /// image name, e.g. `xtool.exe`
+    pub name: String,
+    /// full path of the executable, if known.
+    pub executable_path: Option<PathBuf>,
+    /// full command line, if known.
+    pub command_line: Option<String>,
+    /// when the event was observed.
+    pub timestamp: SystemTime,
+}
+
+impl ProcessEvent {
+    /// a process creation event, observed now.
+    pub fn created(process_id: u32, parent_process_id: u32, name: impl Into<String>) -> Self {
+        Self::new(EventKind::Created, process_id, parent_process_id, name)
+    }
+
+    /// a process exit event, observed now.
+    pub fn exited(process_id: u32, parent_process_id: u32, name: impl Into<String>) -> Self {
+        Self::new(EventKind::Exited, process_id, parent_process_id, name)
+    }
+
+    pub fn new(
+        kind: EventKind,
+        process_id: u32,
+        parent_process_id: u32,
+        name: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            process_id,
+            parent_process_id,
+            name: name.into(),
+            executable_path: None,
+            command_line: None,
+            timestamp: SystemTime::now(),
+        }
+    }
+
+    pub fn with_executable_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.executable_path = Some(path.into());
+        self
+    }
+
+    pub fn with_command_line(mut self, command_line: impl Into<String>) -> Self {
+        self.command_line = Some(command_line.into());
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: SystemTime) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn is_created(&self) -> bool {
+        self.kind == EventKind::Created
+    }
+
+    pub fn is_exited(&self) -> bool {
+        self.kind == EventKind::Exited
+    }
+}
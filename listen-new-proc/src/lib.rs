@@ -1,13 +1,35 @@
 use thiserror::Error;
 
+mod event;
+mod stop;
+mod table;
+
+#[cfg(any(windows, target_os = "linux"))]
 pub mod listen;
 
+/// Linux backend, based on the netlink proc connector, or `/proc` polling.
+#[cfg(target_os = "linux")]
+pub mod linux;
+#[cfg(windows)]
+mod windows;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("io: {0}")]
     IOError(#[from] std::io::Error),
+    #[cfg(windows)]
     #[error("wmi: {0}")]
     WMIError(#[from] wmi::WMIError),
 }
 
-pub use listen::{Process, listen_process_creation};
+pub use event::{EventKind, ProcessEvent};
+pub use stop::{StopHandle, until_stopped};
+pub use table::ProcessTable;
+
+#[cfg(windows)]
+pub use listen::Process;
+#[cfg(any(windows, target_os = "linux"))]
+pub use listen::{
+    listen_process_creation, listen_process_events, process_creation_stream, process_event_stream,
+    process_exit_stream,
+};
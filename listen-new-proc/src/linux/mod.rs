@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use async_channel::Receiver;
+use futures_lite::{Stream, StreamExt};
+
+use crate::{Error, ProcessEvent};
+
+/// Process events from the netlink proc connector.
+pub mod netlink;
+/// Process information from `/proc`, and a polling event source.
+pub mod procfs;
+
+/// interval of `/proc` polling, same as the `WITHIN 1` of WMI.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Stream of process events, fed by a background thread.
+///
+/// The thread stops shortly after the stream is dropped.
+pub type Events = Receiver<Result<ProcessEvent, Error>>;
+
+/// Subscribe to both process creation and exit.
+///
+/// Uses the netlink proc connector, which requires `CAP_NET_ADMIN`,
+/// and falls back to polling `/proc` every [`POLL_INTERVAL`].
+pub fn process_event_stream() -> Result<Events, Error> {
+    netlink::event_stream().or_else(|_| procfs::event_stream(POLL_INTERVAL))
+}
+
+/// Subscribe to process creation, see [`process_event_stream`].
+pub fn process_creation_stream() -> Result<impl Stream<Item = Result<ProcessEvent, Error>>, Error> {
+    Ok(process_event_stream()?.filter(|event| !matches!(event, Ok(e) if e.is_exited())))
+}
+
+/// Subscribe to process exit, see [`process_event_stream`].
+pub fn process_exit_stream() -> Result<impl Stream<Item = Result<ProcessEvent, Error>>, Error> {
+    Ok(process_event_stream()?.filter(|event| !matches!(event, Ok(e) if e.is_created())))
+}
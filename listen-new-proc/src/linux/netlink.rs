@@ -0,0 +1,357 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{Error, EventKind, ProcessEvent};
+
+use super::{Events, procfs};
+
+const CN_IDX_PROC: u32 = 0x1;
+const CN_VAL_PROC: u32 = 0x1;
+const PROC_CN_MCAST_LISTEN: u32 = 0x1;
+
+const PROC_EVENT_NONE: u32 = 0x0;
+const PROC_EVENT_FORK: u32 = 0x1;
+const PROC_EVENT_EXEC: u32 = 0x2;
+const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+
+/// `struct nlmsghdr`
+const NLMSG_HDRLEN: usize = 16;
+/// `struct cn_msg`, without data
+const CN_MSG_LEN: usize = 20;
+/// `what`, `cpu` and `timestamp_ns` of `struct proc_event`
+const PROC_EVENT_HDRLEN: usize = 16;
+
+/// how often the reader thread checks whether the stream was dropped.
+const RECV_TIMEOUT: Duration = Duration::from_secs(1);
+/// how long to wait for the ack of the listen request.
+///
+/// The kernel ignores requests from outside the initial user and pid namespaces,
+/// without an ack.
+const ACK_TIMEOUT: Duration = Duration::from_secs(2);
+/// how long after an `exec` to read the process name.
+///
+/// Wine starts a Windows program as `wine-preloader` and rewrites its command line
+/// to the Windows one afterwards.
+const EXEC_SETTLE: Duration = Duration::from_millis(100);
+
+/// sequence number of the next listen request, to tell its ack from the ones
+/// of other subscribers, which are multicast too.
+static NEXT_SEQ: AtomicU32 = AtomicU32::new(1);
+
+/// A decoded `struct proc_event`, only the parts we care about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcEvent {
+    Fork {
+        parent_tgid: u32,
+        child_pid: u32,
+        child_tgid: u32,
+    },
+    Exec {
+        pid: u32,
+        tgid: u32,
+    },
+    Exit {
+        pid: u32,
+        tgid: u32,
+    },
+    /// any other event, by its `what` field.
+    Other(u32),
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset + 4)?;
+    Some(u32::from_ne_bytes(bytes.try_into().ok()?))
+}
+
+/// Decode a netlink datagram from the proc connector.
+///
+/// `None` if the datagram is truncated.
+pub fn parse_proc_event(datagram: &[u8]) -> Option<ProcEvent> {
+    let event = datagram.get(NLMSG_HDRLEN + CN_MSG_LEN..)?;
+    let what = u32_at(event, 0)?;
+    let data = event.get(PROC_EVENT_HDRLEN..)?;
+
+    let event = match what {
+        PROC_EVENT_FORK => ProcEvent::Fork {
+            parent_tgid: u32_at(data, 4)?,
+            child_pid: u32_at(data, 8)?,
+            child_tgid: u32_at(data, 12)?,
+        },
+        PROC_EVENT_EXEC => ProcEvent::Exec {
+            pid: u32_at(data, 0)?,
+            tgid: u32_at(data, 4)?,
+        },
+        PROC_EVENT_EXIT => ProcEvent::Exit {
+            pid: u32_at(data, 0)?,
+            tgid: u32_at(data, 4)?,
+        },
+        other => ProcEvent::Other(other),
+    };
+    Some(event)
+}
+
+/// Decode the ack of a listen request sent with `seq`, returns its error number,
+/// `0` on success.
+///
+/// `None` for any other datagram, including acks of other requests.
+pub fn parse_ack(datagram: &[u8], seq: u32) -> Option<u32> {
+    let cn_msg = datagram.get(NLMSG_HDRLEN..)?;
+    // the kernel replies with the request's seq, and its ack + 1
+    if u32_at(cn_msg, 8)? != seq || u32_at(cn_msg, 12)? != 1 {
+        return None;
+    }
+
+    let event = cn_msg.get(CN_MSG_LEN..)?;
+    if u32_at(event, 0)? != PROC_EVENT_NONE {
+        return None;
+    }
+    u32_at(event, PROC_EVENT_HDRLEN)
+}
+
+/// The `PROC_CN_MCAST_LISTEN` request.
+fn listen_request(seq: u32) -> Vec<u8> {
+    let len = NLMSG_HDRLEN + CN_MSG_LEN + 4;
+    let mut msg = Vec::with_capacity(len);
+
+    // nlmsghdr: len, type, flags, seq, pid
+    msg.extend((len as u32).to_ne_bytes());
+    msg.extend((libc::NLMSG_DONE as u16).to_ne_bytes());
+    msg.extend(0u16.to_ne_bytes());
+    msg.extend(0u32.to_ne_bytes());
+    msg.extend(std::process::id().to_ne_bytes());
+    // cn_msg: id.idx, id.val, seq, ack, len, flags
+    msg.extend(CN_IDX_PROC.to_ne_bytes());
+    msg.extend(CN_VAL_PROC.to_ne_bytes());
+    msg.extend(seq.to_ne_bytes());
+    msg.extend(0u32.to_ne_bytes());
+    msg.extend(4u16.to_ne_bytes());
+    msg.extend(0u16.to_ne_bytes());
+    // data: enum proc_cn_mcast_op
+    msg.extend(PROC_CN_MCAST_LISTEN.to_ne_bytes());
+
+    msg
+}
+
+fn check(ret: libc::c_int) -> io::Result<libc::c_int> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Open a netlink socket subscribed to the proc connector.
+///
+/// Fails with `PermissionDenied` without `CAP_NET_ADMIN`, and with `TimedOut`
+/// outside the initial namespaces, e.g. in containers.
+fn subscribe() -> io::Result<OwnedFd> {
+    let fd = unsafe {
+        check(libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_CONNECTOR,
+        ))?
+    };
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = CN_IDX_PROC;
+    unsafe {
+        check(libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const _ as *const libc::sockaddr,
+            size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        ))?;
+    }
+
+    let timeout = libc::timeval {
+        tv_sec: RECV_TIMEOUT.as_secs() as _,
+        tv_usec: 0,
+    };
+    unsafe {
+        check(libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            &timeout as *const _ as *const libc::c_void,
+            size_of::<libc::timeval>() as libc::socklen_t,
+        ))?;
+    }
+
+    let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+    let request = listen_request(seq);
+    unsafe {
+        check(libc::send(
+            fd.as_raw_fd(),
+            request.as_ptr() as *const libc::c_void,
+            request.len(),
+            0,
+        ) as libc::c_int)?;
+    }
+    wait_ack(&fd, seq)?;
+
+    Ok(fd)
+}
+
+/// wait for the ack of the listen request sent with `seq`.
+///
+/// Events received meanwhile are dropped, processes started before
+/// the subscription are up to the caller's snapshot anyway.
+fn wait_ack(fd: &OwnedFd, seq: u32) -> io::Result<()> {
+    let deadline = Instant::now() + ACK_TIMEOUT;
+    let mut buf = [0u8; 1024];
+    while Instant::now() < deadline {
+        let len = unsafe {
+            libc::recv(
+                fd.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        };
+        if len < 0 {
+            let e = io::Error::last_os_error();
+            match e.raw_os_error() {
+                Some(libc::EAGAIN | libc::EINTR | libc::ENOBUFS) => continue,
+                _ => return Err(e),
+            }
+        }
+
+        match parse_ack(&buf[..len as usize], seq) {
+            Some(0) => return Ok(()),
+            Some(err) => return Err(io::Error::from_raw_os_error(err as i32)),
+            None => continue,
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        "no ack from the proc connector",
+    ))
+}
+
+/// wait until `fd` has a datagram to read, `false` on timeout.
+fn wait_readable(fd: &OwnedFd, timeout: Duration) -> io::Result<bool> {
+    let mut pollfd = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // round up, so a deadline is not polled for again right before it passes
+    let timeout = timeout.as_micros().div_ceil(1000) as libc::c_int;
+    match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+        ..0 => match io::Error::last_os_error() {
+            e if e.raw_os_error() == Some(libc::EINTR) => Ok(false),
+            e => Err(e),
+        },
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// Subscribe to the proc connector, requires `CAP_NET_ADMIN`.
+///
+/// A creation event is reported shortly after an `exec`, once the image name is
+/// settled, so a process may be reported again if it `exec`s twice. Forked children
+/// which never `exec` are not reported. Exit events are reported for every process,
+/// with an empty name and no parent for the ones not reported as created.
+pub fn event_stream() -> Result<Events, Error> {
+    let fd = subscribe()?;
+    let (tx, rx) = async_channel::unbounded();
+
+    thread::Builder::new()
+        .name("proc-connector".into())
+        .spawn(move || {
+            let mut buf = [0u8; 1024];
+            // pid -> its creation event
+            let mut known = HashMap::<u32, ProcessEvent>::new();
+            // pids which exec'd, with when to read their name, oldest first
+            let mut pending = VecDeque::<(Instant, u32)>::new();
+
+            while !tx.is_closed() {
+                let timeout = pending.front().map_or(RECV_TIMEOUT, |(due, _)| {
+                    due.saturating_duration_since(Instant::now())
+                        .min(RECV_TIMEOUT)
+                });
+                let mut events = Vec::new();
+
+                match wait_readable(&fd, timeout) {
+                    Ok(true) => {
+                        let len = unsafe {
+                            libc::recv(
+                                fd.as_raw_fd(),
+                                buf.as_mut_ptr() as *mut libc::c_void,
+                                buf.len(),
+                                libc::MSG_DONTWAIT,
+                            )
+                        };
+                        if len < 0 {
+                            let e = io::Error::last_os_error();
+                            match e.raw_os_error() {
+                                // interrupted, or events dropped on overrun
+                                Some(libc::EAGAIN | libc::EINTR | libc::ENOBUFS) => {}
+                                _ => {
+                                    let _ = tx.send_blocking(Err(e.into()));
+                                    return;
+                                }
+                            }
+                        } else {
+                            match parse_proc_event(&buf[..len as usize]) {
+                                // threads share the tgid of their process
+                                Some(ProcEvent::Exec { pid, tgid }) if pid == tgid => {
+                                    pending.retain(|(_, p)| *p != pid);
+                                    pending.push_back((Instant::now() + EXEC_SETTLE, pid));
+                                }
+                                Some(ProcEvent::Exit { pid, tgid }) if pid == tgid => {
+                                    pending.retain(|(_, p)| *p != pid);
+                                    let event = match known.remove(&pid) {
+                                        Some(created) => ProcessEvent {
+                                            kind: EventKind::Exited,
+                                            timestamp: SystemTime::now(),
+                                            ..created
+                                        },
+                                        None => ProcessEvent::exited(pid, 0, ""),
+                                    };
+                                    events.push(event);
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        let _ = tx.send_blocking(Err(e.into()));
+                        return;
+                    }
+                }
+
+                let now = Instant::now();
+                while let Some(&(due, pid)) = pending.front() {
+                    if due > now {
+                        break;
+                    }
+                    pending.pop_front();
+                    // gone already, its exit was reported
+                    if let Some(event) = procfs::read_process(pid, EventKind::Created) {
+                        known.insert(pid, event.clone());
+                        events.push(event);
+                    }
+                }
+
+                for event in events {
+                    if tx.send_blocking(Ok(event)).is_err() {
+                        return;
+                    }
+                }
+            }
+        })?;
+
+    Ok(rx)
+}
@@ -0,0 +1,187 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+use crate::{Error, EventKind, ProcessEvent};
+
+use super::Events;
+
+/// Parse `/proc/<pid>/stat`, returns `(comm, ppid)`.
+///
+/// `comm` may contain spaces and parentheses, so it spans
+/// from the first `(` to the last `)`.
+///
+/// ```rust
+/// use listen_new_proc::linux::procfs::parse_stat;
+///
+/// let stat = "42 (xtool (1).exe) S 7 42 42 0 -1 4194304";
+/// assert_eq!(parse_stat(stat), Some(("xtool (1).exe".to_string(), 7)));
+/// ```
+pub fn parse_stat(stat: &str) -> Option<(String, u32)> {
+    let start = stat.find('(')?;
+    let end = stat.rfind(')')?;
+    let comm = stat.get(start + 1..end)?.to_string();
+
+    // after comm: state, ppid, ...
+    let ppid = stat
+        .get(end + 1..)?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()?;
+    Some((comm, ppid))
+}
+
+/// Image name from a NUL separated `/proc/<pid>/cmdline`.
+///
+/// Takes the file name of `argv[0]`, with either `/` or `\` separators,
+/// as Wine reports Windows paths there.
+///
+/// ```rust
+/// use listen_new_proc::linux::procfs::name_from_cmdline;
+///
+/// let cmdline = b"C:\\Games\\setup\\xtool.exe\0-d\0";
+/// assert_eq!(name_from_cmdline(cmdline).as_deref(), Some("xtool.exe"));
+/// ```
+pub fn name_from_cmdline(cmdline: &[u8]) -> Option<String> {
+    let argv0 = cmdline.split(|b| *b == 0).next()?;
+    let argv0 = String::from_utf8_lossy(argv0);
+    let name = argv0.rsplit(['/', '\\']).next()?;
+
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Join NUL separated arguments with spaces.
+pub fn command_line(cmdline: &[u8]) -> Option<String> {
+    let args: Vec<_> = cmdline
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect();
+
+    (!args.is_empty()).then(|| args.join(" "))
+}
+
+/// Read a process from `/proc`, `None` if it has exited already.
+///
+/// Kernel threads, which have no command line, are named after `comm`.
+pub fn read_process(process_id: u32, kind: EventKind) -> Option<ProcessEvent> {
+    let dir = Path::new("/proc").join(process_id.to_string());
+    let (comm, parent_process_id) = parse_stat(&fs::read_to_string(dir.join("stat")).ok()?)?;
+    let cmdline = fs::read(dir.join("cmdline")).unwrap_or_default();
+
+    let name = name_from_cmdline(&cmdline).unwrap_or(comm);
+    let mut event = ProcessEvent::new(kind, process_id, parent_process_id, name);
+    event.executable_path = fs::read_link(dir.join("exe")).ok();
+    event.command_line = command_line(&cmdline);
+    Some(event)
+}
+
+/// Snapshot of running processes, by PID.
+pub fn snapshot() -> Result<HashMap<u32, ProcessEvent>, Error> {
+    let mut processes = HashMap::new();
+    for entry in fs::read_dir("/proc")? {
+        let Some(process_id) = entry?.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        if let Some(process) = read_process(process_id, EventKind::Created) {
+            processes.insert(process_id, process);
+        }
+    }
+    Ok(processes)
+}
+
+/// Events between two snapshots: exits first, then creations, parents before their children.
+///
+/// A PID present in both with another parent was reused, it produces both an exit
+/// and a creation event. With the same parent but another name the process exec'd,
+/// it only produces a creation event.
+pub fn diff(
+    previous: &HashMap<u32, ProcessEvent>,
+    current: &HashMap<u32, ProcessEvent>,
+) -> Vec<ProcessEvent> {
+    let alive =
+        |old: &ProcessEvent, new: &ProcessEvent| old.parent_process_id == new.parent_process_id;
+    let same = |old: &ProcessEvent, new: &ProcessEvent| alive(old, new) && old.name == new.name;
+
+    let mut exited: Vec<_> = previous
+        .iter()
+        .filter_map(|(pid, old)| match current.get(pid) {
+            Some(new) if alive(old, new) => None,
+            _ => Some(ProcessEvent {
+                kind: EventKind::Exited,
+                ..old.clone()
+            }),
+        })
+        .collect();
+    exited.sort_by_key(|e| e.process_id);
+
+    let mut created: Vec<_> = current
+        .iter()
+        .filter(|(pid, new)| !previous.get(pid).is_some_and(|old| same(old, new)))
+        .map(|(pid, _)| *pid)
+        .collect();
+    created.sort_unstable();
+
+    let mut events = exited;
+    events.extend(parents_first(&created, current).map(|pid| current[&pid].clone()));
+    events
+}
+
+/// order `created` so that a process comes after its parent, when both are in it.
+///
+/// Pids wrap around, so a child may have a lower pid than its parent.
+fn parents_first<'a>(
+    created: &'a [u32],
+    current: &'a HashMap<u32, ProcessEvent>,
+) -> impl Iterator<Item = u32> + 'a {
+    let pending: HashSet<u32> = created.iter().copied().collect();
+    let mut emitted = HashSet::with_capacity(created.len());
+
+    created.iter().flat_map(move |&pid| {
+        // walk up to the oldest ancestor not emitted yet
+        let mut chain = vec![];
+        let mut next = pid;
+        while pending.contains(&next) && emitted.insert(next) {
+            chain.push(next);
+            next = current[&next].parent_process_id;
+        }
+        chain.into_iter().rev()
+    })
+}
+
+/// Poll `/proc` for process creation and exit, every `interval`.
+///
+/// Processes living shorter than `interval` may be missed.
+pub fn event_stream(interval: Duration) -> Result<Events, Error> {
+    let mut previous = snapshot()?;
+    let (tx, rx) = async_channel::unbounded();
+
+    thread::Builder::new()
+        .name("procfs-poll".into())
+        .spawn(move || {
+            while !tx.is_closed() {
+                thread::sleep(interval);
+
+                let current = match snapshot() {
+                    Ok(current) => current,
+                    Err(e) => {
+                        let _ = tx.send_blocking(Err(e));
+                        return;
+                    }
+                };
+                for event in diff(&previous, &current) {
+                    if tx.send_blocking(Ok(event)).is_err() {
+                        return;
+                    }
+                }
+                previous = current;
+            }
+        })?;
+
+    Ok(rx)
+}
@@ -1,41 +1,39 @@
-use std::{collections::HashMap, time::Duration};
-
 use futures_lite::StreamExt;
-use serde::Deserialize;
-use wmi::{COMLibrary, FilterValue, WMIConnection};
 
-use crate::Error;
+use crate::{Error, ProcessEvent, StopHandle, until_stopped};
 
-#[derive(Deserialize, Debug)]
-#[serde(rename = "__InstanceCreationEvent")]
-#[serde(rename_all = "PascalCase")]
-struct NewProcessEvent {
-    target_instance: Process,
-}
+#[cfg(target_os = "linux")]
+pub use crate::linux::{process_creation_stream, process_event_stream, process_exit_stream};
+#[cfg(windows)]
+pub use crate::windows::{
+    Process, process_creation_stream, process_event_stream, process_exit_stream,
+};
 
-#[derive(Deserialize, Debug, Default)]
-#[serde(rename = "Win32_Process")]
-#[serde(rename_all = "PascalCase")]
-#[non_exhaustive]
-pub struct Process {
-    pub process_id: u32,
-    pub parent_process_id: u32,
-    pub name: String,
-}
+/// Call `callback` on every new process, until `stop` is requested.
+pub async fn listen_process_creation(
+    mut callback: impl FnMut(ProcessEvent),
+    stop: &StopHandle,
+) -> Result<(), Error> {
+    let stream = Box::pin(process_creation_stream()?);
+    let mut stream = until_stopped(stream, stop.clone());
 
-pub async fn listen_process_creation(mut callback: impl FnMut(Process)) -> Result<(), Error> {
-    let com_con = COMLibrary::new()?;
-    let wmi_con = WMIConnection::new(com_con)?;
+    while let Some(result) = stream.next().await {
+        callback(result?);
+    }
 
-    let mut filters = HashMap::<String, FilterValue>::new();
-    filters.insert("TargetInstance".to_owned(), FilterValue::is_a::<Process>()?);
+    Ok(())
+}
 
-    let mut stream = wmi_con
-        .async_filtered_notification::<NewProcessEvent>(&filters, Some(Duration::from_secs(1)))?;
+/// Like [`listen_process_creation`], with exit events as well.
+pub async fn listen_process_events(
+    mut callback: impl FnMut(ProcessEvent),
+    stop: &StopHandle,
+) -> Result<(), Error> {
+    let stream = Box::pin(process_event_stream()?);
+    let mut stream = until_stopped(stream, stop.clone());
 
     while let Some(result) = stream.next().await {
-        let process = result?.target_instance;
-        callback(process);
+        callback(result?);
     }
 
     Ok(())
@@ -0,0 +1,71 @@
+use async_channel::{Receiver, Sender};
+use futures_lite::{Stream, StreamExt, future, stream};
+
+/// Stops listeners and streams, from any task or thread.
+///
+/// Clones share the same state, stopping one stops them all.
+///
+/// ```rust
+/// use futures_lite::{StreamExt, future, stream};
+/// use listen_new_proc::{StopHandle, until_stopped};
+///
+/// let stop = StopHandle::new();
+/// let mut events = until_stopped(stream::pending::<u32>(), stop.clone());
+///
+/// stop.stop();
+/// assert_eq!(future::block_on(events.next()), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct StopHandle {
+    sender: Sender<()>,
+    receiver: Receiver<()>,
+}
+
+impl Default for StopHandle {
+    fn default() -> Self {
+        // nothing is ever sent, closing the channel wakes every waiter
+        let (sender, receiver) = async_channel::bounded(1);
+        Self { sender, receiver }
+    }
+}
+
+impl StopHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// request a stop, calling it again does nothing.
+    pub fn stop(&self) {
+        self.sender.close();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// wait until [`StopHandle::stop`] is called, returns immediately if it was.
+    pub async fn stopped(&self) {
+        let _ = self.receiver.recv().await;
+    }
+}
+
+/// Ends `stream` once `stop` is requested, even while waiting for an item.
+///
+/// Items already ready when the stop is requested are dropped.
+pub fn until_stopped<S: Stream + Unpin>(
+    stream: S,
+    stop: StopHandle,
+) -> impl Stream<Item = S::Item> + Unpin {
+    Box::pin(stream::unfold(
+        (stream, stop),
+        |(mut stream, stop)| async move {
+            let stopped = async {
+                stop.stopped().await;
+                None
+            };
+            // stop first, if both are ready
+            let item = future::or(stopped, stream.next()).await?;
+            Some((item, (stream, stop)))
+        },
+    ))
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use crate::{EventKind, ProcessEvent};
+
+/// Per-process state, forgotten once the process exits.
+///
+/// ```rust
+/// use listen_new_proc::{ProcessEvent, ProcessTable};
+///
+/// let mut table = ProcessTable::new();
+/// table.insert(42, "throttled");
+///
+/// table.observe(&ProcessEvent::exited(42, 4, "xtool.exe"));
+/// assert!(table.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProcessTable<T> {
+    entries: HashMap<u32, T>,
+}
+
+impl<T> Default for ProcessTable<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<T> ProcessTable<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, process_id: u32, value: T) -> Option<T> {
+        self.entries.insert(process_id, value)
+    }
+
+    pub fn get(&self, process_id: u32) -> Option<&T> {
+        self.entries.get(&process_id)
+    }
+
+    pub fn get_mut(&mut self, process_id: u32) -> Option<&mut T> {
+        self.entries.get_mut(&process_id)
+    }
+
+    pub fn remove(&mut self, process_id: u32) -> Option<T> {
+        self.entries.remove(&process_id)
+    }
+
+    pub fn contains(&self, process_id: u32) -> bool {
+        self.entries.contains_key(&process_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        self.entries.iter().map(|(pid, value)| (*pid, value))
+    }
+
+    /// Update the table with an event, returns the forgotten entry.
+    ///
+    /// An exit event forgets the process. A creation event forgets the
+    /// previous owner of a reused PID, in case its exit was missed.
+    pub fn observe(&mut self, event: &ProcessEvent) -> Option<T> {
+        match event.kind {
+            EventKind::Created | EventKind::Exited => self.remove(event.process_id),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::{collections::HashMap, time::Duration};
+
+use futures_lite::{Stream, StreamExt, stream};
+use serde::{Deserialize, de::DeserializeOwned};
+use wmi::{COMLibrary, FilterValue, WMIConnection};
+
+use crate::{Error, EventKind, ProcessEvent};
+
+#[derive(Deserialize, Debug)]
+#[serde(rename = "__InstanceCreationEvent")]
+#[serde(rename_all = "PascalCase")]
+struct NewProcessEvent {
+    target_instance: Process,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename = "__InstanceDeletionEvent")]
+#[serde(rename_all = "PascalCase")]
+struct ExitProcessEvent {
+    target_instance: Process,
+}
+
+/// `Win32_Process` instance from WMI.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename = "Win32_Process")]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct Process {
+    pub process_id: u32,
+    pub parent_process_id: u32,
+    pub name: String,
+    /// `None` if access is denied, e.g. for system processes.
+    pub executable_path: Option<String>,
+    /// `None` if access is denied, e.g. for system processes.
+    pub command_line: Option<String>,
+}
+
+impl Process {
+    /// convert into an event, timestamped on receipt.
+    pub fn into_event(self, kind: EventKind) -> ProcessEvent {
+        let Process {
+            process_id,
+            parent_process_id,
+            name,
+            executable_path,
+            command_line,
+        } = self;
+        let mut event = ProcessEvent::new(kind, process_id, parent_process_id, name);
+        event.executable_path = executable_path.map(Into::into);
+        event.command_line = command_line;
+        event
+    }
+}
+
+fn connect() -> Result<WMIConnection, Error> {
+    let com_con = COMLibrary::new()?;
+    Ok(WMIConnection::new(com_con)?)
+}
+
+fn subscribe<T: DeserializeOwned>(
+    wmi_con: &WMIConnection,
+) -> Result<impl Stream<Item = Result<T, Error>> + use<T>, Error> {
+    let mut filters = HashMap::<String, FilterValue>::new();
+    filters.insert("TargetInstance".to_owned(), FilterValue::is_a::<Process>()?);
+
+    let stream =
+        wmi_con.async_filtered_notification::<T>(&filters, Some(Duration::from_secs(1)))?;
+
+    Ok(stream.map(|result| Ok(result?)))
+}
+
+/// Subscribe to process creation with WMI, as a stream of events.
+pub fn process_creation_stream() -> Result<impl Stream<Item = Result<ProcessEvent, Error>>, Error> {
+    let stream = subscribe::<NewProcessEvent>(&connect()?)?;
+
+    Ok(stream.map(|result| Ok(result?.target_instance.into_event(EventKind::Created))))
+}
+
+/// Subscribe to process exit with WMI, as a stream of events.
+pub fn process_exit_stream() -> Result<impl Stream<Item = Result<ProcessEvent, Error>>, Error> {
+    let stream = subscribe::<ExitProcessEvent>(&connect()?)?;
+
+    Ok(stream.map(|result| Ok(result?.target_instance.into_event(EventKind::Exited))))
+}
+
+/// Subscribe to both process creation and exit with WMI,
+/// events are yielded as soon as either of them arrives,
+/// creation first if both are ready.
+pub fn process_event_stream() -> Result<impl Stream<Item = Result<ProcessEvent, Error>>, Error> {
+    let wmi_con = connect()?;
+    let created = subscribe::<NewProcessEvent>(&wmi_con)?
+        .map(|result| Ok(result?.target_instance.into_event(EventKind::Created)));
+    let exited = subscribe::<ExitProcessEvent>(&wmi_con)?
+        .map(|result| Ok(result?.target_instance.into_event(EventKind::Exited)));
+
+    Ok(stream::or(created, exited))
+}
@@ -0,0 +1,39 @@
+use std::{path::Path, time::SystemTime};
+
+use listen_new_proc::{EventKind, ProcessEvent};
+
+#[test]
+fn created_and_exited() {
+    let created = ProcessEvent::created(42, 4, "xtool.exe");
+    assert_eq!(created.kind, EventKind::Created);
+    assert!(created.is_created());
+    assert!(!created.is_exited());
+    assert_eq!((created.process_id, created.parent_process_id), (42, 4));
+    assert_eq!(created.name, "xtool.exe");
+
+    let exited = ProcessEvent::exited(42, 4, "xtool.exe");
+    assert!(exited.is_exited());
+    assert_ne!(created.kind, exited.kind);
+}
+
+#[test]
+fn optional_fields_default_to_none() {
+    let event = ProcessEvent::created(1, 0, "setup.tmp");
+    assert_eq!(event.executable_path, None);
+    assert_eq!(event.command_line, None);
+}
+
+#[test]
+fn builder_fills_optional_fields() {
+    let event = ProcessEvent::created(1, 0, "setup.tmp")
+        .with_executable_path(r"C:\Users\me\AppData\Local\Temp\is-1\setup.tmp")
+        .with_command_line(r#""setup.tmp" /SL5"#)
+        .with_timestamp(SystemTime::UNIX_EPOCH);
+
+    assert_eq!(
+        event.executable_path.as_deref(),
+        Some(Path::new(r"C:\Users\me\AppData\Local\Temp\is-1\setup.tmp"))
+    );
+    assert_eq!(event.command_line.as_deref(), Some(r#""setup.tmp" /SL5"#));
+    assert_eq!(event.timestamp, SystemTime::UNIX_EPOCH);
+}
@@ -0,0 +1,268 @@
+#![cfg(target_os = "linux")]
+
+use std::{
+    collections::HashMap,
+    io,
+    process::Command,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures_lite::future::block_on;
+use listen_new_proc::{
+    EventKind, ProcessEvent,
+    linux::{
+        Events,
+        netlink::{self, ProcEvent, parse_ack, parse_proc_event},
+        procfs::{self, command_line, diff, name_from_cmdline, parse_stat},
+    },
+};
+
+/// nlmsghdr + cn_msg + proc_event header, followed by `data`.
+fn datagram(what: u32, data: &[u32]) -> Vec<u8> {
+    let mut buf = vec![0u8; 16 + 20];
+    buf.extend(what.to_ne_bytes());
+    buf.extend(3u32.to_ne_bytes());
+    buf.extend(123456789u64.to_ne_bytes());
+    for word in data {
+        buf.extend(word.to_ne_bytes());
+    }
+    buf
+}
+
+#[test]
+fn parse_fork_exec_exit() {
+    assert_eq!(
+        parse_proc_event(&datagram(0x1, &[100, 100, 200, 200])),
+        Some(ProcEvent::Fork {
+            parent_tgid: 100,
+            child_pid: 200,
+            child_tgid: 200,
+        })
+    );
+    assert_eq!(
+        parse_proc_event(&datagram(0x2, &[201, 200])),
+        Some(ProcEvent::Exec {
+            pid: 201,
+            tgid: 200
+        })
+    );
+    assert_eq!(
+        parse_proc_event(&datagram(0x8000_0000, &[200, 200, 0, 17])),
+        Some(ProcEvent::Exit {
+            pid: 200,
+            tgid: 200
+        })
+    );
+    assert_eq!(
+        parse_proc_event(&datagram(0x0, &[0, 0])),
+        Some(ProcEvent::Other(0))
+    );
+}
+
+/// the kernel's reply to a listen request, with `seq`.
+fn ack(seq: u32, ack: u32, err: u32) -> Vec<u8> {
+    let mut buf = vec![0u8; 16];
+    // cn_msg: id.idx, id.val, seq, ack, len, flags
+    buf.extend(1u32.to_ne_bytes());
+    buf.extend(1u32.to_ne_bytes());
+    buf.extend(seq.to_ne_bytes());
+    buf.extend(ack.to_ne_bytes());
+    buf.extend(4u16.to_ne_bytes());
+    buf.extend(0u16.to_ne_bytes());
+    // proc_event: what, cpu, timestamp_ns, then the error of PROC_EVENT_NONE
+    buf.extend(0u32.to_ne_bytes());
+    buf.extend(0u32.to_ne_bytes());
+    buf.extend(0u64.to_ne_bytes());
+    buf.extend(err.to_ne_bytes());
+    buf
+}
+
+#[test]
+fn parse_listen_ack() {
+    assert_eq!(parse_ack(&ack(7, 1, 0), 7), Some(0));
+    // EPERM, in a container
+    assert_eq!(parse_ack(&ack(7, 1, 1), 7), Some(1));
+    // another subscriber's ack
+    assert_eq!(parse_ack(&ack(8, 1, 0), 7), None);
+    assert_eq!(parse_ack(&ack(7, 0, 0), 7), None);
+    assert_eq!(parse_ack(&datagram(0x1, &[100, 100, 200, 200]), 0), None);
+    assert_eq!(parse_ack(&[0; 16], 7), None);
+}
+
+#[test]
+fn parse_truncated_datagram() {
+    assert_eq!(parse_proc_event(&[0; 16]), None);
+    assert_eq!(parse_proc_event(&datagram(0x2, &[201])), None);
+}
+
+#[test]
+fn parse_stat_line() {
+    assert_eq!(
+        parse_stat("1234 (wine64-preload) S 1 1234 1234 0 -1"),
+        Some(("wine64-preload".to_string(), 1))
+    );
+    assert_eq!(
+        parse_stat("77 (a) b) R 76 77 77"),
+        Some(("a) b".to_string(), 76))
+    );
+    assert_eq!(parse_stat("garbage"), None);
+}
+
+#[test]
+fn name_and_command_line() {
+    assert_eq!(
+        name_from_cmdline(b"/usr/bin/sleep\x0010\x00").as_deref(),
+        Some("sleep")
+    );
+    assert_eq!(
+        name_from_cmdline(b"Z:\\games\\repack\\oo2reck.exe\x00").as_deref(),
+        Some("oo2reck.exe")
+    );
+    assert_eq!(name_from_cmdline(b""), None);
+
+    assert_eq!(
+        command_line(b"xtool.exe\x00-d\x00-m\x00").as_deref(),
+        Some("xtool.exe -d -m")
+    );
+    assert_eq!(command_line(b""), None);
+}
+
+#[test]
+fn diff_snapshots() {
+    let previous = HashMap::from([
+        (1, ProcessEvent::created(1, 0, "init")),
+        (10, ProcessEvent::created(10, 1, "old.exe")),
+        (20, ProcessEvent::created(20, 1, "gone.exe")),
+        (40, ProcessEvent::created(40, 1, "setup.exe")),
+    ]);
+    let current = HashMap::from([
+        (1, ProcessEvent::created(1, 0, "init")),
+        (10, ProcessEvent::created(10, 40, "reused.exe")),
+        (30, ProcessEvent::created(30, 1, "new.exe")),
+        // exec'd, same parent
+        (40, ProcessEvent::created(40, 1, "setup.tmp")),
+    ]);
+
+    let mut events: Vec<_> = diff(&previous, &current)
+        .into_iter()
+        .map(|e| (e.kind, e.process_id, e.name))
+        .collect();
+    events.sort_by_key(|(kind, pid, _)| (*kind == EventKind::Created, *pid));
+
+    assert_eq!(
+        events,
+        [
+            (EventKind::Exited, 10, "old.exe".to_string()),
+            (EventKind::Exited, 20, "gone.exe".to_string()),
+            (EventKind::Created, 10, "reused.exe".to_string()),
+            (EventKind::Created, 30, "new.exe".to_string()),
+            (EventKind::Created, 40, "setup.tmp".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn diff_creates_parents_first() {
+    // pids wrapped around, children got lower ones
+    let current = HashMap::from([
+        (1, ProcessEvent::created(1, 0, "init")),
+        (30, ProcessEvent::created(30, 40, "child.exe")),
+        (40, ProcessEvent::created(40, 50, "setup.tmp")),
+        (50, ProcessEvent::created(50, 1, "setup.exe")),
+        (20, ProcessEvent::created(20, 50, "sibling.exe")),
+    ]);
+    let previous = HashMap::from([(1, ProcessEvent::created(1, 0, "init"))]);
+
+    let pids: Vec<_> = diff(&previous, &current)
+        .into_iter()
+        .map(|e| e.process_id)
+        .collect();
+
+    let position = |pid| pids.iter().position(|p| *p == pid).unwrap();
+    assert_eq!(pids.len(), 4);
+    assert!(position(50) < position(40));
+    assert!(position(40) < position(30));
+    assert!(position(50) < position(20));
+}
+
+#[test]
+fn snapshot_contains_self() {
+    let processes = procfs::snapshot().unwrap();
+    let me = &processes[&std::process::id()];
+
+    assert_eq!(me.kind, EventKind::Created);
+    assert_eq!(
+        me.executable_path.as_deref(),
+        std::env::current_exe().ok().as_deref()
+    );
+    assert!(me.command_line.is_some());
+}
+
+/// spawn a short lived child, collect its events from `events`.
+fn child_events(events: Events) -> Vec<ProcessEvent> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        block_on(async {
+            while let Ok(Ok(event)) = events.recv().await {
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+        })
+    });
+
+    let mut child = Command::new("sleep").arg("0.5").spawn().unwrap();
+    let pid = child.id();
+    child.wait().unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut seen = vec![];
+    while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+        let Ok(event) = rx.recv_timeout(timeout) else {
+            break;
+        };
+        if event.process_id == pid {
+            let exited = event.is_exited();
+            seen.push(event);
+            if exited {
+                break;
+            }
+        }
+    }
+    seen
+}
+
+#[test]
+fn procfs_reports_child_lifecycle() {
+    let events = child_events(procfs::event_stream(Duration::from_millis(100)).unwrap());
+
+    let kinds: Vec<_> = events.iter().map(|e| e.kind).collect();
+    assert_eq!(kinds, [EventKind::Created, EventKind::Exited]);
+    assert_eq!(events[0].name, "sleep");
+    assert_eq!(events[0].parent_process_id, std::process::id());
+}
+
+#[test]
+fn netlink_reports_child_lifecycle() {
+    // requires CAP_NET_ADMIN, in the initial namespaces
+    let events = match netlink::event_stream() {
+        Ok(events) => events,
+        Err(listen_new_proc::Error::IOError(e))
+            if matches!(
+                e.kind(),
+                io::ErrorKind::PermissionDenied | io::ErrorKind::TimedOut
+            ) =>
+        {
+            eprintln!("skipped, can't subscribe to the proc connector: {e}");
+            return;
+        }
+        Err(e) => panic!("subscribing to the proc connector: {e}"),
+    };
+    let events = child_events(events);
+
+    let kinds: Vec<_> = events.iter().map(|e| e.kind).collect();
+    assert_eq!(kinds, [EventKind::Created, EventKind::Exited]);
+    assert_eq!(events[0].name, "sleep");
+}
@@ -0,0 +1,39 @@
+use futures_lite::{StreamExt, future, stream};
+use listen_new_proc::{StopHandle, until_stopped};
+
+#[test]
+fn stop_ends_pending_stream() {
+    let stop = StopHandle::new();
+    let mut items = until_stopped(stream::pending::<u32>(), stop.clone());
+
+    let waiter = std::thread::spawn({
+        let stop = stop.clone();
+        move || future::block_on(stop.stopped())
+    });
+    stop.stop();
+    waiter.join().unwrap();
+
+    assert!(stop.is_stopped());
+    assert_eq!(future::block_on(items.next()), None);
+}
+
+#[test]
+fn items_pass_through_until_stopped() {
+    let stop = StopHandle::new();
+    let mut items = until_stopped(stream::iter([1, 2, 3]), stop.clone());
+
+    assert_eq!(future::block_on(items.next()), Some(1));
+    stop.stop();
+    assert_eq!(future::block_on(items.next()), None);
+}
+
+#[test]
+fn clones_share_state() {
+    let stop = StopHandle::new();
+    let other = stop.clone();
+
+    other.stop();
+    other.stop();
+    assert!(stop.is_stopped());
+    future::block_on(stop.stopped());
+}
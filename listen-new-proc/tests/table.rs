@@ -0,0 +1,40 @@
+use listen_new_proc::{ProcessEvent, ProcessTable};
+
+#[test]
+fn exit_forgets_process() {
+    let mut table = ProcessTable::new();
+    table.insert(10, "a");
+    table.insert(11, "b");
+
+    assert_eq!(
+        table.observe(&ProcessEvent::exited(10, 1, "a.exe")),
+        Some("a")
+    );
+    assert!(!table.contains(10));
+    assert_eq!(table.get(11), Some(&"b"));
+    assert_eq!(table.len(), 1);
+}
+
+#[test]
+fn exit_of_unknown_process_is_noop() {
+    let mut table = ProcessTable::new();
+    table.insert(10, ());
+
+    assert_eq!(
+        table.observe(&ProcessEvent::exited(99, 1, "other.exe")),
+        None
+    );
+    assert_eq!(table.len(), 1);
+}
+
+#[test]
+fn reused_pid_drops_stale_entry() {
+    let mut table = ProcessTable::new();
+    table.insert(10, "old");
+
+    assert_eq!(
+        table.observe(&ProcessEvent::created(10, 1, "new.exe")),
+        Some("old")
+    );
+    assert!(table.is_empty());
+}
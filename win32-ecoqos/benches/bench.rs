@@ -1,4 +1,5 @@
 #![feature(test)]
+#![cfg(windows)]
 
 use win32_ecoqos::process::{toggle_efficiency_mode, toggle_efficiency_mode_handle};
 use windows::Win32::{
@@ -1,5 +1,12 @@
+#[cfg(windows)]
 use win32_ecoqos::utils::{Process, Processes};
 
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("this example only runs on Windows");
+}
+
+#[cfg(windows)]
 fn main() -> windows_result::Result<()> {
     let processes: Vec<_> = Processes::try_new()?.collect();
 
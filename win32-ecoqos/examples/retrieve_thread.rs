@@ -1,6 +1,9 @@
+#[cfg(windows)]
 use std::{error::Error, sync::mpsc::channel, thread::sleep, time::Duration};
 
+#[cfg(windows)]
 use win32_ecoqos::thread::{ecoqos_enabled, toggle_efficiency_mode_handle};
+#[cfg(windows)]
 use windows::Win32::{
     Foundation::CloseHandle,
     System::Threading::{
@@ -8,6 +11,12 @@ use windows::Win32::{
     },
 };
 
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("this example only runs on Windows");
+}
+
+#[cfg(windows)]
 fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     let (tx, rx) = channel();
     let _ = std::thread::spawn(move || {
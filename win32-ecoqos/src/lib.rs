@@ -1,18 +1,26 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(feature = "nightly", feature(doc_cfg))]
 
+#[cfg(windows)]
 pub use windows;
 pub use windows_result;
 
+#[cfg(windows)]
 pub(crate) mod preset;
 
+/// Platform independent description of EcoQoS states and priority classes.
+pub mod qos;
+
 /// Process related EcoQoS toggle functions.
+#[cfg(windows)]
 pub mod process;
 /// Threading related EcoQoS toggle functions.
+#[cfg(windows)]
 pub mod thread;
 
 /// Helper functions to deal with processes/threads.
 ///
 /// For a full example to open thread after obtained Win32 ThreadID,
 /// see [retrieve_thread.rs](https://github.com/mokurin000/fitgirl-ecoqos/blob/master/win32-ecoqos/examples/retrieve_thread.rs)
+#[cfg(windows)]
 pub mod utils;
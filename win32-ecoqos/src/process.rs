@@ -1,39 +1,80 @@
 use std::ffi::c_void;
 
-use crate::preset::{PROCESS_RESTORE, PROCESS_THROTTLE, PROCESS_UNTHROTTLE};
+use crate::preset::process_state;
+pub use crate::qos::{PriorityClass, ProcessQos, QosState, ThrottlingState};
+#[cfg(feature = "find_thread")]
+use crate::{thread, utils::Threads};
+#[cfg(feature = "find_thread")]
+use windows::Win32::System::Threading::{OpenThread, THREAD_SET_INFORMATION};
 use windows::Win32::{
-    Foundation::{CloseHandle, HANDLE},
+    Foundation::{CloseHandle, E_UNEXPECTED, HANDLE},
     System::Threading::{
-        GetProcessInformation, OpenProcess, ProcessPowerThrottling, SetPriorityClass,
-        SetProcessInformation, IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS, PROCESS_CREATION_FLAGS,
-        PROCESS_INFORMATION_CLASS, PROCESS_POWER_THROTTLING_CURRENT_VERSION,
-        PROCESS_POWER_THROTTLING_EXECUTION_SPEED, PROCESS_POWER_THROTTLING_STATE,
-        PROCESS_SET_INFORMATION,
+        GetPriorityClass, GetProcessInformation, OpenProcess, ProcessPowerThrottling,
+        SetPriorityClass, SetProcessInformation, PROCESS_CREATION_FLAGS,
+        PROCESS_POWER_THROTTLING_CURRENT_VERSION, PROCESS_POWER_THROTTLING_EXECUTION_SPEED,
+        PROCESS_POWER_THROTTLING_STATE, PROCESS_QUERY_INFORMATION, PROCESS_SET_INFORMATION,
     },
 };
 
-unsafe fn toggle_efficiency_mode_impl(
-    hprocess: HANDLE,
-    processinformation: *const c_void,
-    processinformationclass: PROCESS_INFORMATION_CLASS,
-    processinformationsize: u32,
-    dwpriorityclass: PROCESS_CREATION_FLAGS,
-) -> Result<(), windows_result::Error> {
-    unsafe {
-        SetProcessInformation(
-            hprocess,
-            processinformationclass,
-            processinformation,
-            processinformationsize,
-        )?;
-        SetPriorityClass(hprocess, dwpriorityclass)?;
+impl ProcessQos {
+    /// Apply on a process, by it's PID.
+    ///
+    /// ```rust
+    /// use win32_ecoqos::process::{PriorityClass, ProcessQos};
+    ///
+    /// let pid = std::process::id();
+    ///
+    /// // Enable EcoQoS, keep priority class
+    /// ProcessQos::new().ecoqos(Some(true)).apply(pid);
+    /// // Let system decide, with below normal priority
+    /// ProcessQos::new()
+    ///     .ecoqos(None)
+    ///     .priority(PriorityClass::BelowNormal)
+    ///     .apply(pid);
+    /// ```
+    pub fn apply(&self, pid: u32) -> Result<(), windows_result::Error> {
+        let hprocess = unsafe { OpenProcess(PROCESS_SET_INFORMATION, false, pid)? };
+        let result = unsafe { self.apply_handle(hprocess) };
+        let _ = unsafe { CloseHandle(hprocess) };
+
+        result
     }
 
-    Ok(())
+    /// Apply on a process, by a [`HANDLE`](https://microsoft.github.io/windows-docs-rs/doc/windows/Win32/Foundation/struct.HANDLE.html).
+    ///
+    /// The handle must have [`PROCESS_SET_INFORMATION`](https://microsoft.github.io/windows-docs-rs/doc/windows/Win32/System/Threading/constant.PROCESS_SET_INFORMATION.html)
+    /// access right.
+    ///
+    /// # Safety
+    ///
+    /// `hprocess` must be a valid process handle. DO NOT pass null ptr.
+    pub unsafe fn apply_handle(&self, hprocess: HANDLE) -> Result<(), windows_result::Error> {
+        if let Some(state) = self.throttling_state() {
+            let new_state = process_state(state);
+            unsafe {
+                SetProcessInformation(
+                    hprocess,
+                    ProcessPowerThrottling,
+                    &new_state as *const _ as *const c_void,
+                    size_of::<PROCESS_POWER_THROTTLING_STATE>() as u32,
+                )?;
+            }
+        }
+
+        if let Some(priority) = self.priority_class() {
+            unsafe {
+                SetPriorityClass(hprocess, PROCESS_CREATION_FLAGS(priority.as_raw()))?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 /// Toggle efficiency mode of a process, by it's PID.
 ///
+/// Efficiency mode also sets the priority class, see [`ProcessQos::efficiency_mode`].
+///
 /// ```rust
 /// use win32_ecoqos::process::toggle_efficiency_mode;
 ///
@@ -54,6 +95,89 @@ pub fn toggle_efficiency_mode(pid: u32, enable: Option<bool>) -> Result<(), wind
     result
 }
 
+/// Result of toggling a single thread, see [`toggle_all_threads`].
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "find_thread")))]
+#[cfg(feature = "find_thread")]
+#[derive(Debug)]
+pub struct ThreadToggle {
+    /// win32 thread id
+    pub thread_id: u32,
+    pub result: Result<(), windows_result::Error>,
+}
+
+/// Per-thread results of [`toggle_all_threads`], in snapshot order.
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "find_thread")))]
+#[cfg(feature = "find_thread")]
+#[derive(Debug, Default)]
+pub struct ThreadsReport {
+    pub threads: Vec<ThreadToggle>,
+}
+
+#[cfg(feature = "find_thread")]
+impl ThreadsReport {
+    /// ids of threads which were toggled.
+    pub fn succeeded(&self) -> impl Iterator<Item = u32> + '_ {
+        self.threads
+            .iter()
+            .filter(|t| t.result.is_ok())
+            .map(|t| t.thread_id)
+    }
+
+    /// ids of threads which failed, with their error.
+    pub fn failed(&self) -> impl Iterator<Item = (u32, &windows_result::Error)> + '_ {
+        self.threads
+            .iter()
+            .filter_map(|t| t.result.as_ref().err().map(|e| (t.thread_id, e)))
+    }
+
+    /// whether every thread was toggled.
+    pub fn is_complete(&self) -> bool {
+        self.threads.iter().all(|t| t.result.is_ok())
+    }
+}
+
+/// Toggle efficiency mode of every thread of a process, by it's PID.
+///
+/// Some programs set `ThreadPowerThrottling` on their threads, which takes precedence
+/// over the process setting. Each thread is opened with `THREAD_SET_INFORMATION`
+/// and toggled with [`thread::toggle_efficiency_mode_handle`], a failing thread
+/// doesn't stop the others, e.g. when it exited meanwhile. Only taking the snapshot
+/// may fail as a whole.
+///
+/// ```rust
+/// use win32_ecoqos::process::toggle_all_threads;
+///
+/// let report = toggle_all_threads(std::process::id(), Some(true)).unwrap();
+/// for (thread_id, e) in report.failed() {
+///     eprintln!("failed to throttle thread {thread_id}: {e}");
+/// }
+/// assert!(report.succeeded().count() > 0);
+/// ```
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "find_thread")))]
+#[cfg(feature = "find_thread")]
+pub fn toggle_all_threads(
+    pid: u32,
+    enable: Option<bool>,
+) -> Result<ThreadsReport, windows_result::Error> {
+    let threads = Threads::for_process(pid)?
+        .of_process()
+        .map(|t| {
+            let result = unsafe { OpenThread(THREAD_SET_INFORMATION, false, t.thread_id) }
+                .and_then(|hthread| {
+                    let result = unsafe { thread::toggle_efficiency_mode_handle(hthread, enable) };
+                    let _ = unsafe { CloseHandle(hthread) };
+                    result
+                });
+            ThreadToggle {
+                thread_id: t.thread_id,
+                result,
+            }
+        })
+        .collect();
+
+    Ok(ThreadsReport { threads })
+}
+
 /// Toggle efficiency mode of a process, by a [`HANDLE`](https://microsoft.github.io/windows-docs-rs/doc/windows/Win32/Foundation/struct.HANDLE.html).
 ///
 /// [`GetCurrentProcess`]: https://microsoft.github.io/windows-docs-rs/doc/windows/Win32/System/Threading/fn.GetCurrentProcess.html
@@ -62,7 +186,13 @@ pub fn toggle_efficiency_mode(pid: u32, enable: Option<bool>) -> Result<(), wind
 /// You must enable [`PROCESS_SET_INFORMATION`](https://microsoft.github.io/windows-docs-rs/doc/windows/Win32/System/Threading/constant.PROCESS_SET_INFORMATION.html)
 /// access flag on your handle to apply EcoQoS throttle.
 ///
-/// SAFETY: `hprocess` must be a valid process handle. DO NOT pass null ptr, e.g.
+/// Same as `ProcessQos::efficiency_mode(enable).apply_handle(hprocess)`,
+/// which also resets the priority class to idle or normal.
+/// Use [`ProcessQos`] to keep the priority class untouched.
+///
+/// # Safety
+///
+/// `hprocess` must be a valid process handle. DO NOT pass null ptr, e.g.
 ///
 /// ```rust
 /// use win32_ecoqos::process::toggle_efficiency_mode_handle;
@@ -87,39 +217,33 @@ pub unsafe fn toggle_efficiency_mode_handle(
     hprocess: HANDLE,
     enable: Option<bool>,
 ) -> Result<(), windows_result::Error> {
-    let new_state = match enable {
-        Some(true) => PROCESS_THROTTLE,
-        Some(false) => PROCESS_UNTHROTTLE,
-        None => PROCESS_RESTORE,
-    };
-
-    let processinformationclass = ProcessPowerThrottling;
-    let processinformation = &new_state as *const _ as *const c_void;
-    let processinformationsize = size_of::<PROCESS_POWER_THROTTLING_STATE>() as u32;
-
-    let dwpriorityclass = if let Some(true) = enable {
-        IDLE_PRIORITY_CLASS
-    } else {
-        NORMAL_PRIORITY_CLASS
-    };
-
-    unsafe {
-        toggle_efficiency_mode_impl(
-            hprocess,
-            processinformation,
-            processinformationclass,
-            processinformationsize,
-            dwpriorityclass,
-        )
-    }
+    unsafe { ProcessQos::efficiency_mode(enable).apply_handle(hprocess) }
 }
 
 /// check whether EcoQoS is enabled on a process.
 ///
 /// `hprocess` must have `PROCESS_QUERY_INFORMATION` access right.
 ///
-/// SAFETY: `hprocess` must be a valid process handle. DO NOT pass null ptr, e.g.
+/// Use [`throttling_state`] to tell system-managed from HighQoS.
+///
+/// # Safety
+///
+/// `hprocess` must be a valid process handle. DO NOT pass null ptr.
 pub unsafe fn ecoqos_enabled(hprocess: HANDLE) -> Result<bool, windows_result::Error> {
+    let state = unsafe { throttling_state(hprocess)? };
+
+    Ok(state.state_mask & PROCESS_POWER_THROTTLING_EXECUTION_SPEED
+        == PROCESS_POWER_THROTTLING_EXECUTION_SPEED)
+}
+
+/// get the throttling state of a process, with every bit it controls.
+///
+/// `hprocess` must have `PROCESS_QUERY_INFORMATION` access right.
+///
+/// # Safety
+///
+/// `hprocess` must be a valid process handle. DO NOT pass null ptr.
+pub unsafe fn throttling_state(hprocess: HANDLE) -> Result<ThrottlingState, windows_result::Error> {
     let mut process_info = PROCESS_POWER_THROTTLING_STATE {
         Version: PROCESS_POWER_THROTTLING_CURRENT_VERSION,
         ControlMask: 0,
@@ -135,8 +259,44 @@ pub unsafe fn ecoqos_enabled(hprocess: HANDLE) -> Result<bool, windows_result::E
         )?;
     }
 
-    Ok(
-        process_info.StateMask & PROCESS_POWER_THROTTLING_EXECUTION_SPEED
-            == PROCESS_POWER_THROTTLING_EXECUTION_SPEED,
-    )
+    Ok(ThrottlingState {
+        control_mask: process_info.ControlMask,
+        state_mask: process_info.StateMask,
+    })
+}
+
+/// Query the throttling state of a process, by it's PID.
+///
+/// ```rust
+/// use win32_ecoqos::process::{query_qos, toggle_efficiency_mode, QosState};
+///
+/// let pid = std::process::id();
+///
+/// toggle_efficiency_mode(pid, Some(false)).unwrap();
+/// assert_eq!(query_qos(pid).unwrap().qos(), QosState::High);
+/// toggle_efficiency_mode(pid, None).unwrap();
+/// assert_eq!(query_qos(pid).unwrap().qos(), QosState::Default);
+/// ```
+pub fn query_qos(pid: u32) -> Result<ThrottlingState, windows_result::Error> {
+    let hprocess = unsafe { OpenProcess(PROCESS_QUERY_INFORMATION, false, pid)? };
+    let result = unsafe { throttling_state(hprocess) };
+    let _ = unsafe { CloseHandle(hprocess) };
+
+    result
+}
+
+/// get the priority class of a process.
+///
+/// `hprocess` must have `PROCESS_QUERY_INFORMATION` or
+/// `PROCESS_QUERY_LIMITED_INFORMATION` access right.
+///
+/// # Safety
+///
+/// `hprocess` must be a valid process handle. DO NOT pass null ptr.
+pub unsafe fn priority_class(hprocess: HANDLE) -> Result<PriorityClass, windows_result::Error> {
+    let raw = unsafe { GetPriorityClass(hprocess) };
+    if raw == 0 {
+        return Err(windows_result::Error::from_win32());
+    }
+    PriorityClass::from_raw(raw).ok_or_else(|| E_UNEXPECTED.into())
 }
@@ -0,0 +1,286 @@
+/// `PROCESS_POWER_THROTTLING_EXECUTION_SPEED`, same value for threads.
+pub const EXECUTION_SPEED: u32 = 0x1;
+/// `PROCESS_POWER_THROTTLING_IGNORE_TIMER_RESOLUTION`, processes only.
+pub const IGNORE_TIMER_RESOLUTION: u32 = 0x4;
+
+/// EcoQoS state of a process or thread, the `EXECUTION_SPEED` bit of a [`ThrottlingState`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum QosState {
+    /// Managed by the system.
+    #[default]
+    Default,
+    /// EcoQoS, explicitly enabled.
+    Eco,
+    /// HighQoS, EcoQoS explicitly disabled.
+    High,
+}
+
+impl QosState {
+    /// `Some(true)` for EcoQoS, `Some(false)` for HighQoS, `None` if managed by the system.
+    pub const fn from_enable(enable: Option<bool>) -> Self {
+        match enable {
+            Some(true) => Self::Eco,
+            Some(false) => Self::High,
+            None => Self::Default,
+        }
+    }
+
+    /// the `enable` argument of `toggle_efficiency_mode` which leads to this state.
+    pub const fn enable(self) -> Option<bool> {
+        match self {
+            Self::Eco => Some(true),
+            Self::High => Some(false),
+            Self::Default => None,
+        }
+    }
+}
+
+/// Platform independent description of a
+/// [`PROCESS_POWER_THROTTLING_STATE`](https://learn.microsoft.com/en-us/windows/win32/api/processthreadsapi/ns-processthreadsapi-process_power_throttling_state).
+///
+/// ```rust
+/// use win32_ecoqos::qos::{ThrottlingState, EXECUTION_SPEED, IGNORE_TIMER_RESOLUTION};
+///
+/// let state = ThrottlingState::from_enable(Some(true));
+/// assert_eq!(state.control_mask, EXECUTION_SPEED);
+/// assert_eq!(state.state_mask, EXECUTION_SPEED);
+///
+/// // EcoQoS, and ignore timer resolution requests
+/// let state = state.with_ignore_timer_resolution(Some(true));
+/// assert_eq!(state.control_mask, EXECUTION_SPEED | IGNORE_TIMER_RESOLUTION);
+/// assert_eq!(state.state_mask, EXECUTION_SPEED | IGNORE_TIMER_RESOLUTION);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ThrottlingState {
+    /// bits controlled by the caller, the others are left to the system.
+    pub control_mask: u32,
+    /// bits to enable, a subset of `control_mask`.
+    pub state_mask: u32,
+}
+
+impl ThrottlingState {
+    /// Enable EcoQoS.
+    pub const THROTTLE: Self = Self {
+        control_mask: EXECUTION_SPEED,
+        state_mask: EXECUTION_SPEED,
+    };
+    /// Enable HighQoS.
+    pub const UNTHROTTLE: Self = Self {
+        control_mask: EXECUTION_SPEED,
+        state_mask: 0,
+    };
+    /// Let system decide.
+    pub const RESTORE: Self = Self {
+        control_mask: 0,
+        state_mask: 0,
+    };
+
+    /// `Some(true)` to enable EcoQoS, `Some(false)` to enable HighQoS,
+    /// `None` to let system decide.
+    pub const fn from_enable(enable: Option<bool>) -> Self {
+        match enable {
+            Some(true) => Self::THROTTLE,
+            Some(false) => Self::UNTHROTTLE,
+            None => Self::RESTORE,
+        }
+    }
+
+    /// control a throttling bit, e.g. [`IGNORE_TIMER_RESOLUTION`]: `Some(true)` to enable it,
+    /// `Some(false)` to disable it, `None` to leave it to the system.
+    ///
+    /// Other bits are kept.
+    pub const fn with_flag(mut self, bit: u32, enable: Option<bool>) -> Self {
+        match enable {
+            Some(true) => {
+                self.control_mask |= bit;
+                self.state_mask |= bit;
+            }
+            Some(false) => {
+                self.control_mask |= bit;
+                self.state_mask &= !bit;
+            }
+            None => {
+                self.control_mask &= !bit;
+                self.state_mask &= !bit;
+            }
+        }
+        self
+    }
+
+    /// set EcoQoS, see [`ThrottlingState::from_enable`], other bits are kept.
+    pub const fn with_execution_speed(self, enable: Option<bool>) -> Self {
+        self.with_flag(EXECUTION_SPEED, enable)
+    }
+
+    /// `Some(true)` to ignore timer resolution requests of the process,
+    /// `Some(false)` to honor them, `None` to let system decide. Processes only.
+    pub const fn with_ignore_timer_resolution(self, ignore: Option<bool>) -> Self {
+        self.with_flag(IGNORE_TIMER_RESOLUTION, ignore)
+    }
+
+    /// a throttling bit, e.g. [`IGNORE_TIMER_RESOLUTION`]: `Some(true)` if enabled,
+    /// `Some(false)` if disabled, `None` if left to the system.
+    pub const fn flag(&self, bit: u32) -> Option<bool> {
+        if self.control_mask & bit == 0 {
+            None
+        } else {
+            Some(self.state_mask & bit != 0)
+        }
+    }
+
+    /// EcoQoS state, from the [`EXECUTION_SPEED`] bit.
+    ///
+    /// ```rust
+    /// use win32_ecoqos::qos::{QosState, ThrottlingState};
+    ///
+    /// assert_eq!(ThrottlingState::THROTTLE.qos(), QosState::Eco);
+    /// assert_eq!(ThrottlingState::UNTHROTTLE.qos(), QosState::High);
+    /// assert_eq!(ThrottlingState::RESTORE.qos(), QosState::Default);
+    /// ```
+    pub const fn qos(&self) -> QosState {
+        QosState::from_enable(self.flag(EXECUTION_SPEED))
+    }
+
+    /// whether timer resolution requests are ignored, from the [`IGNORE_TIMER_RESOLUTION`] bit.
+    pub const fn ignore_timer_resolution(&self) -> Option<bool> {
+        self.flag(IGNORE_TIMER_RESOLUTION)
+    }
+}
+
+/// Win32 process priority class.
+///
+/// See [`SetPriorityClass`](https://learn.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-setpriorityclass).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PriorityClass {
+    Idle,
+    BelowNormal,
+    Normal,
+    AboveNormal,
+    High,
+    Realtime,
+}
+
+impl PriorityClass {
+    /// raw `PROCESS_CREATION_FLAGS` value of this class.
+    pub const fn as_raw(self) -> u32 {
+        match self {
+            Self::Idle => 0x40,
+            Self::BelowNormal => 0x4000,
+            Self::Normal => 0x20,
+            Self::AboveNormal => 0x8000,
+            Self::High => 0x80,
+            Self::Realtime => 0x100,
+        }
+    }
+
+    /// parse a value returned by `GetPriorityClass`.
+    pub const fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0x40 => Some(Self::Idle),
+            0x4000 => Some(Self::BelowNormal),
+            0x20 => Some(Self::Normal),
+            0x8000 => Some(Self::AboveNormal),
+            0x80 => Some(Self::High),
+            0x100 => Some(Self::Realtime),
+            _ => None,
+        }
+    }
+}
+
+/// Builder of the QoS to apply on a process.
+///
+/// Nothing is changed unless requested, e.g. to enable EcoQoS
+/// and keep the current priority class:
+///
+/// ```rust
+/// use win32_ecoqos::qos::{PriorityClass, ProcessQos, ThrottlingState};
+///
+/// let qos = ProcessQos::new().ecoqos(Some(true));
+/// assert_eq!(qos.throttling_state(), Some(ThrottlingState::THROTTLE));
+/// assert_eq!(qos.priority_class(), None);
+///
+/// // efficiency mode = EcoQoS + idle priority
+/// let qos = ProcessQos::efficiency_mode(Some(true));
+/// assert_eq!(qos.priority_class(), Some(PriorityClass::Idle));
+/// ```
+///
+/// Apply it with `ProcessQos::apply` on Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProcessQos {
+    throttling: Option<ThrottlingState>,
+    priority: Option<PriorityClass>,
+}
+
+impl ProcessQos {
+    /// create a builder which changes nothing.
+    pub const fn new() -> Self {
+        Self {
+            throttling: None,
+            priority: None,
+        }
+    }
+
+    /// Preset used by `toggle_efficiency_mode`:
+    ///
+    /// `Some(true)` pairs EcoQoS with [`PriorityClass::Idle`],
+    /// otherwise [`PriorityClass::Normal`] is set.
+    pub const fn efficiency_mode(enable: Option<bool>) -> Self {
+        let priority = match enable {
+            Some(true) => PriorityClass::Idle,
+            _ => PriorityClass::Normal,
+        };
+        Self::new().ecoqos(enable).priority(priority)
+    }
+
+    /// set EcoQoS, see [`ThrottlingState::from_enable`].
+    ///
+    /// Other throttling bits already set on the builder are kept.
+    pub const fn ecoqos(self, enable: Option<bool>) -> Self {
+        self.throttling(self.base_throttling().with_execution_speed(enable))
+    }
+
+    /// ignore timer resolution requests, see [`ThrottlingState::with_ignore_timer_resolution`].
+    ///
+    /// ```rust
+    /// use win32_ecoqos::qos::{ProcessQos, ThrottlingState};
+    ///
+    /// let qos = ProcessQos::efficiency_mode(Some(true)).ignore_timer_resolution(Some(true));
+    /// assert_eq!(
+    ///     qos.throttling_state(),
+    ///     Some(ThrottlingState::THROTTLE.with_ignore_timer_resolution(Some(true)))
+    /// );
+    /// ```
+    pub const fn ignore_timer_resolution(self, ignore: Option<bool>) -> Self {
+        self.throttling(self.base_throttling().with_ignore_timer_resolution(ignore))
+    }
+
+    /// the throttling state being built, every bit left to the system if none yet.
+    const fn base_throttling(&self) -> ThrottlingState {
+        match self.throttling {
+            Some(state) => state,
+            None => ThrottlingState::RESTORE,
+        }
+    }
+
+    /// set a custom throttling state.
+    pub const fn throttling(mut self, state: ThrottlingState) -> Self {
+        self.throttling = Some(state);
+        self
+    }
+
+    /// set the priority class.
+    pub const fn priority(mut self, priority: PriorityClass) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// throttling state to set, if any.
+    pub const fn throttling_state(&self) -> Option<ThrottlingState> {
+        self.throttling
+    }
+
+    /// priority class to set, if any.
+    pub const fn priority_class(&self) -> Option<PriorityClass> {
+        self.priority
+    }
+}
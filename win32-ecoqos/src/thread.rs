@@ -1,16 +1,21 @@
 use std::ffi::c_void;
 
-use crate::preset::{THREAD_RESTORE, THREAD_THROTTLE, THREAD_UNTHROTTLE};
+use crate::preset::{thread_state, THREAD_RESTORE, THREAD_THROTTLE, THREAD_UNTHROTTLE};
+pub use crate::qos::{QosState, ThrottlingState};
 use windows::Win32::{
     Foundation::{CloseHandle, HANDLE},
     System::Threading::{
-        GetThreadInformation, OpenThread, SetThreadInformation, SetThreadPriority,
-        ThreadPowerThrottling, THREAD_INFORMATION_CLASS, THREAD_POWER_THROTTLING_CURRENT_VERSION,
-        THREAD_POWER_THROTTLING_EXECUTION_SPEED, THREAD_POWER_THROTTLING_STATE, THREAD_PRIORITY,
-        THREAD_PRIORITY_IDLE, THREAD_PRIORITY_NORMAL, THREAD_SET_INFORMATION,
+        GetThreadInformation, GetThreadPriority, OpenThread, SetThreadInformation,
+        SetThreadPriority, ThreadPowerThrottling, THREAD_INFORMATION_CLASS,
+        THREAD_POWER_THROTTLING_CURRENT_VERSION, THREAD_POWER_THROTTLING_EXECUTION_SPEED,
+        THREAD_POWER_THROTTLING_STATE, THREAD_PRIORITY, THREAD_PRIORITY_IDLE,
+        THREAD_PRIORITY_NORMAL, THREAD_QUERY_INFORMATION, THREAD_SET_INFORMATION,
     },
 };
 
+/// returned by `GetThreadPriority` on failure.
+const THREAD_PRIORITY_ERROR_RETURN: i32 = 0x7FFF_FFFF;
+
 unsafe fn toggle_efficiency_mode_impl(
     hthread: HANDLE,
     threadinformation: *const c_void,
@@ -57,7 +62,9 @@ pub fn toggle_efficiency_mode(
 /// You must enable [`THREAD_SET_INFORMATION`](https://microsoft.github.io/windows-docs-rs/doc/windows/Win32/System/Threading/constant.THREAD_SET_INFORMATION.html)
 /// access flag on the handle to apply EcoQoS throttle.
 ///
-/// SAFETY: `hthread` must be a valid process handle. DO NOT pass null ptr, e.g.
+/// # Safety
+///
+/// `hthread` must be a valid thread handle. DO NOT pass null ptr.
 pub unsafe fn toggle_efficiency_mode_handle(
     hthread: HANDLE,
     enable: Option<bool>,
@@ -93,8 +100,26 @@ pub unsafe fn toggle_efficiency_mode_handle(
 ///
 /// `hprocess` must have `THREAD_QUERY_INFORMATION` access right.
 ///
-/// SAFETY: `hthread` must be a valid process handle. DO NOT pass null ptr, e.g.
+/// Use [`throttling_state`] to tell system-managed from HighQoS.
+///
+/// # Safety
+///
+/// `hthread` must be a valid thread handle. DO NOT pass null ptr.
 pub unsafe fn ecoqos_enabled(hthread: HANDLE) -> Result<bool, windows_result::Error> {
+    let state = unsafe { throttling_state(hthread)? };
+
+    Ok(state.state_mask & THREAD_POWER_THROTTLING_EXECUTION_SPEED
+        == THREAD_POWER_THROTTLING_EXECUTION_SPEED)
+}
+
+/// get the throttling state of a thread.
+///
+/// `hthread` must have `THREAD_QUERY_INFORMATION` access right.
+///
+/// # Safety
+///
+/// `hthread` must be a valid thread handle. DO NOT pass null ptr.
+pub unsafe fn throttling_state(hthread: HANDLE) -> Result<ThrottlingState, windows_result::Error> {
     let mut thread_info = THREAD_POWER_THROTTLING_STATE {
         Version: THREAD_POWER_THROTTLING_CURRENT_VERSION,
         ..Default::default()
@@ -109,8 +134,67 @@ pub unsafe fn ecoqos_enabled(hthread: HANDLE) -> Result<bool, windows_result::Er
         )?;
     }
 
-    Ok(
-        thread_info.StateMask & THREAD_POWER_THROTTLING_EXECUTION_SPEED
-            == THREAD_POWER_THROTTLING_EXECUTION_SPEED,
-    )
+    Ok(ThrottlingState {
+        control_mask: thread_info.ControlMask,
+        state_mask: thread_info.StateMask,
+    })
+}
+
+/// Query the throttling state of a thread, by a thread_id.
+pub fn query_qos(thread_id: u32) -> Result<ThrottlingState, windows_result::Error> {
+    let hthread = unsafe { OpenThread(THREAD_QUERY_INFORMATION, false, thread_id)? };
+    let result = unsafe { throttling_state(hthread) };
+    let _ = unsafe { CloseHandle(hthread) };
+
+    result
+}
+
+/// get the priority of a thread, e.g. `THREAD_PRIORITY_NORMAL`.
+///
+/// `hthread` must have `THREAD_QUERY_INFORMATION` or
+/// `THREAD_QUERY_LIMITED_INFORMATION` access right.
+///
+/// # Safety
+///
+/// `hthread` must be a valid thread handle. DO NOT pass null ptr.
+pub unsafe fn priority(hthread: HANDLE) -> Result<THREAD_PRIORITY, windows_result::Error> {
+    let raw = unsafe { GetThreadPriority(hthread) };
+    if raw == THREAD_PRIORITY_ERROR_RETURN {
+        return Err(windows_result::Error::from_win32());
+    }
+    Ok(THREAD_PRIORITY(raw))
+}
+
+/// Query the priority of a thread, by a thread_id.
+pub fn query_priority(thread_id: u32) -> Result<THREAD_PRIORITY, windows_result::Error> {
+    let hthread = unsafe { OpenThread(THREAD_QUERY_INFORMATION, false, thread_id)? };
+    let result = unsafe { priority(hthread) };
+    let _ = unsafe { CloseHandle(hthread) };
+
+    result
+}
+
+/// Set the throttling state and priority of a thread, by a thread_id.
+///
+/// e.g. to put back the ones returned by [`query_qos`] and [`query_priority`],
+/// after [`toggle_efficiency_mode`].
+pub fn set_qos(
+    thread_id: u32,
+    state: ThrottlingState,
+    priority: THREAD_PRIORITY,
+) -> Result<(), windows_result::Error> {
+    let new_state = thread_state(state);
+    let hthread = unsafe { OpenThread(THREAD_SET_INFORMATION, false, thread_id)? };
+    let result = unsafe {
+        toggle_efficiency_mode_impl(
+            hthread,
+            &new_state as *const _ as *const c_void,
+            ThreadPowerThrottling,
+            size_of::<THREAD_POWER_THROTTLING_STATE>() as u32,
+            priority,
+        )
+    };
+    let _ = unsafe { CloseHandle(hthread) };
+
+    result
 }
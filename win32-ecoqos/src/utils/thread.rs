@@ -4,7 +4,7 @@ use std::{
 };
 
 use windows::Win32::{
-    Foundation::{CloseHandle, HANDLE},
+    Foundation::{CloseHandle, LocalFree, HANDLE, HLOCAL},
     System::{
         Diagnostics::ToolHelp::{
             CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD, THREADENTRY32,
@@ -42,10 +42,13 @@ pub struct Thread {
 ///         .is_some()
 /// );
 /// ```
+///
+/// Threads of another process can be found with [`Threads::for_process`].
 #[derive(Debug)]
 pub struct Threads {
     snapshot: HANDLE,
     last_entry: Option<THREADENTRY32>,
+    process_id: u32,
 }
 
 impl Drop for Threads {
@@ -55,44 +58,64 @@ impl Drop for Threads {
 }
 
 impl Thread {
+    /// name of the thread, empty if it has none.
+    ///
+    /// Threads of other processes need the `THREAD_QUERY_LIMITED_INFORMATION` access right.
     pub fn get_name(&self) -> windows_result::Result<OsString> {
         unsafe {
             let hthread = OpenThread(THREAD_QUERY_LIMITED_INFORMATION, false, self.thread_id)?;
-            let description = GetThreadDescription(hthread)?;
+            let description = GetThreadDescription(hthread);
+            let _ = CloseHandle(hthread);
+            let description = description?;
 
-            Ok(OsString::from_wide(description.as_wide()))
+            let name = OsString::from_wide(description.as_wide());
+            LocalFree(Some(HLOCAL(description.0 as _)));
+            Ok(name)
         }
     }
 }
 
 impl Threads {
-    /// create a new snapshop to find threads
+    /// create a new snapshop to find threads of current process
     pub fn try_new() -> windows_result::Result<Self> {
-        let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, std::process::id()) }?;
+        Self::for_process(std::process::id())
+    }
+
+    /// create a new snapshop to find threads of a process.
+    ///
+    /// The snapshot iterates threads of every process,
+    /// see [`Threads::of_process`] to only keep the ones of `process_id`.
+    pub fn for_process(process_id: u32) -> windows_result::Result<Self> {
+        let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, process_id) }?;
         Ok(Self {
             snapshot,
             last_entry: None,
+            process_id,
         })
     }
 
-    /// find a thread of current process, by it's name
+    /// threads of the process the snapshot was created for
+    pub fn of_process(self) -> impl Iterator<Item = Thread> {
+        let process_id = self.process_id;
+        self.filter(move |t| t.owner_process_id == process_id)
+    }
+
+    /// find a thread of the process the snapshot was created for, by it's name
     pub fn find_thread_by_name<'a>(
         self,
         thread_name: &'a OsStr,
         full_match: bool,
     ) -> impl Iterator<Item = Thread> + 'a {
-        let process_id = std::process::id();
-        self.filter(move |t| t.owner_process_id == process_id)
-            .filter(move |t| {
-                t.get_name().is_ok_and(|name| {
-                    if full_match {
-                        &name == thread_name
-                    } else {
-                        name.to_string_lossy()
-                            .contains(thread_name.to_string_lossy().as_ref())
-                    }
-                })
+        self.of_process().filter(move |t| {
+            t.get_name().is_ok_and(|name| {
+                if full_match {
+                    name == thread_name
+                } else {
+                    name.to_string_lossy()
+                        .contains(thread_name.to_string_lossy().as_ref())
+                }
             })
+        })
     }
 }
 
@@ -0,0 +1,44 @@
+#![cfg(windows)]
+
+use std::{sync::mpsc::channel, thread::sleep, time::Duration};
+
+use win32_ecoqos::{process::toggle_all_threads, thread::ecoqos_enabled};
+use windows::Win32::{
+    Foundation::CloseHandle,
+    System::Threading::{GetCurrentThreadId, OpenThread, THREAD_QUERY_INFORMATION},
+};
+
+fn thread_ecoqos(thread_id: u32) -> windows_result::Result<bool> {
+    unsafe {
+        let hthread = OpenThread(THREAD_QUERY_INFORMATION, false, thread_id)?;
+        let enabled = ecoqos_enabled(hthread);
+        let _ = CloseHandle(hthread);
+        enabled
+    }
+}
+
+#[test]
+fn toggle_every_thread_of_process() -> windows_result::Result<()> {
+    let (tx, rx) = channel();
+    let _ = std::thread::spawn(move || {
+        let _ = tx.send(unsafe { GetCurrentThreadId() });
+        loop {
+            sleep(Duration::from_secs(5));
+        }
+    });
+    let thread_id = rx.recv().expect("failed to retrieve thread id");
+    let current = unsafe { GetCurrentThreadId() };
+
+    let report = toggle_all_threads(std::process::id(), Some(true))?;
+    assert!(report.is_complete());
+    let toggled: Vec<_> = report.succeeded().collect();
+    assert!(toggled.contains(&thread_id));
+    assert!(toggled.contains(&current));
+    assert!(thread_ecoqos(thread_id)?);
+
+    let report = toggle_all_threads(std::process::id(), Some(false))?;
+    assert_eq!(report.failed().count(), 0);
+    assert!(!thread_ecoqos(thread_id)?);
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+#![cfg(windows)]
+
 use std::{ffi::OsString, sync::mpsc::channel, thread::sleep, time::Duration};
 
 use win32_ecoqos::utils::{Thread, Threads};
@@ -31,3 +33,22 @@ fn find_thread_by_name_snap() -> windows_result::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn threads_of_another_process() -> windows_result::Result<()> {
+    let mut child = std::process::Command::new("cmd")
+        .args(["/c", "ping -n 30 127.0.0.1 >NUL"])
+        .spawn()
+        .expect("failed to spawn cmd");
+    let process_id = child.id();
+
+    let threads = Threads::for_process(process_id).map(|t| t.of_process().collect::<Vec<_>>());
+    let _ = child.kill();
+    let _ = child.wait();
+
+    let threads = threads?;
+    assert!(!threads.is_empty());
+    assert!(threads.iter().all(|t| t.owner_process_id == process_id));
+
+    Ok(())
+}
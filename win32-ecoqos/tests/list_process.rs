@@ -1,3 +1,5 @@
+#![cfg(windows)]
+
 use win32_ecoqos::utils::Processes;
 
 #[test]
@@ -1,4 +1,8 @@
-use win32_ecoqos::process::{ecoqos_enabled, toggle_efficiency_mode_handle};
+#![cfg(windows)]
+
+use win32_ecoqos::process::{
+    ecoqos_enabled, priority_class, toggle_efficiency_mode_handle, PriorityClass, ProcessQos,
+};
 use windows::Win32::System::Threading::GetCurrentProcess;
 
 #[test]
@@ -14,3 +18,22 @@ pub fn test_process_ecoqos() -> Result<(), windows_result::Error> {
 
     Ok(())
 }
+
+#[test]
+pub fn test_process_priority_class() -> Result<(), windows_result::Error> {
+    unsafe {
+        let hprocess = GetCurrentProcess();
+        let original = priority_class(hprocess)?;
+
+        toggle_efficiency_mode_handle(hprocess, Some(true))?;
+        assert_eq!(priority_class(hprocess)?, PriorityClass::Idle);
+
+        ProcessQos::new()
+            .ecoqos(None)
+            .priority(original)
+            .apply_handle(hprocess)?;
+        assert_eq!(priority_class(hprocess)?, original);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use win32_ecoqos::qos::{
+    PriorityClass, ProcessQos, QosState, ThrottlingState, EXECUTION_SPEED, IGNORE_TIMER_RESOLUTION,
+};
+
+#[test]
+fn throttling_state_from_enable() {
+    assert_eq!(
+        ThrottlingState::from_enable(Some(true)),
+        ThrottlingState {
+            control_mask: EXECUTION_SPEED,
+            state_mask: EXECUTION_SPEED,
+        }
+    );
+    assert_eq!(
+        ThrottlingState::from_enable(Some(false)),
+        ThrottlingState {
+            control_mask: EXECUTION_SPEED,
+            state_mask: 0,
+        }
+    );
+    assert_eq!(
+        ThrottlingState::from_enable(None),
+        ThrottlingState::default()
+    );
+}
+
+#[test]
+fn builder_changes_nothing_by_default() {
+    let qos = ProcessQos::new();
+    assert_eq!(qos.throttling_state(), None);
+    assert_eq!(qos.priority_class(), None);
+}
+
+#[test]
+fn builder_keeps_priority_untouched() {
+    let qos = ProcessQos::new().ecoqos(Some(true));
+    assert_eq!(qos.throttling_state(), Some(ThrottlingState::THROTTLE));
+    assert_eq!(qos.priority_class(), None);
+
+    let qos = ProcessQos::new().priority(PriorityClass::High);
+    assert_eq!(qos.throttling_state(), None);
+    assert_eq!(qos.priority_class(), Some(PriorityClass::High));
+}
+
+#[test]
+fn efficiency_mode_preset() {
+    let eco = ProcessQos::efficiency_mode(Some(true));
+    assert_eq!(eco.throttling_state(), Some(ThrottlingState::THROTTLE));
+    assert_eq!(eco.priority_class(), Some(PriorityClass::Idle));
+
+    let high = ProcessQos::efficiency_mode(Some(false));
+    assert_eq!(high.throttling_state(), Some(ThrottlingState::UNTHROTTLE));
+    assert_eq!(high.priority_class(), Some(PriorityClass::Normal));
+
+    let restore = ProcessQos::efficiency_mode(None);
+    assert_eq!(restore.throttling_state(), Some(ThrottlingState::RESTORE));
+    assert_eq!(restore.priority_class(), Some(PriorityClass::Normal));
+}
+
+#[test]
+fn priority_class_raw_roundtrip() {
+    for class in [
+        PriorityClass::Idle,
+        PriorityClass::BelowNormal,
+        PriorityClass::Normal,
+        PriorityClass::AboveNormal,
+        PriorityClass::High,
+        PriorityClass::Realtime,
+    ] {
+        assert_eq!(PriorityClass::from_raw(class.as_raw()), Some(class));
+    }
+    assert_eq!(PriorityClass::from_raw(0), None);
+}
+
+#[test]
+fn qos_state_from_masks() {
+    assert_eq!(ThrottlingState::THROTTLE.qos(), QosState::Eco);
+    assert_eq!(ThrottlingState::UNTHROTTLE.qos(), QosState::High);
+    assert_eq!(ThrottlingState::RESTORE.qos(), QosState::Default);
+
+    // state bits outside the control mask are left to the system
+    let state = ThrottlingState {
+        control_mask: 0,
+        state_mask: EXECUTION_SPEED,
+    };
+    assert_eq!(state.qos(), QosState::Default);
+
+    for enable in [Some(true), Some(false), None] {
+        let state = ThrottlingState::from_enable(enable);
+        assert_eq!(state.qos(), QosState::from_enable(enable));
+        assert_eq!(state.qos().enable(), enable);
+    }
+}
+
+#[test]
+fn other_throttling_bits() {
+    let state = ThrottlingState {
+        control_mask: EXECUTION_SPEED | IGNORE_TIMER_RESOLUTION,
+        state_mask: IGNORE_TIMER_RESOLUTION,
+    };
+    assert_eq!(state.qos(), QosState::High);
+    assert_eq!(state.ignore_timer_resolution(), Some(true));
+    assert_eq!(state.flag(IGNORE_TIMER_RESOLUTION), Some(true));
+
+    let state = ThrottlingState {
+        control_mask: IGNORE_TIMER_RESOLUTION,
+        state_mask: 0,
+    };
+    assert_eq!(state.qos(), QosState::Default);
+    assert_eq!(state.ignore_timer_resolution(), Some(false));
+    assert_eq!(ThrottlingState::THROTTLE.ignore_timer_resolution(), None);
+}
+
+#[test]
+fn flags_combine() {
+    let state = ThrottlingState::RESTORE
+        .with_execution_speed(Some(true))
+        .with_ignore_timer_resolution(Some(true));
+    assert_eq!(
+        state,
+        ThrottlingState {
+            control_mask: EXECUTION_SPEED | IGNORE_TIMER_RESOLUTION,
+            state_mask: EXECUTION_SPEED | IGNORE_TIMER_RESOLUTION,
+        }
+    );
+
+    let state = state.with_execution_speed(Some(false));
+    assert_eq!(state.qos(), QosState::High);
+    assert_eq!(state.ignore_timer_resolution(), Some(true));
+
+    let state = state.with_ignore_timer_resolution(None);
+    assert_eq!(state, ThrottlingState::UNTHROTTLE);
+    assert_eq!(
+        ThrottlingState::THROTTLE.with_flag(EXECUTION_SPEED, None),
+        ThrottlingState::RESTORE
+    );
+}
+
+#[test]
+fn builder_keeps_other_flags() {
+    let expected = ThrottlingState::UNTHROTTLE.with_ignore_timer_resolution(Some(true));
+
+    let qos = ProcessQos::new()
+        .ignore_timer_resolution(Some(true))
+        .ecoqos(Some(false));
+    assert_eq!(qos.throttling_state(), Some(expected));
+
+    let qos = ProcessQos::new()
+        .ecoqos(Some(false))
+        .ignore_timer_resolution(Some(true));
+    assert_eq!(qos.throttling_state(), Some(expected));
+    assert_eq!(qos.priority_class(), None);
+}
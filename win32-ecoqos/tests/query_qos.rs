@@ -0,0 +1,74 @@
+#![cfg(windows)]
+
+use std::{sync::mpsc::channel, thread::sleep, time::Duration};
+
+use win32_ecoqos::{
+    process::{self, toggle_efficiency_mode, QosState},
+    thread,
+};
+use windows::Win32::System::Threading::{
+    GetCurrentThread, GetCurrentThreadId, SetThreadPriority, THREAD_PRIORITY_ABOVE_NORMAL,
+    THREAD_PRIORITY_IDLE,
+};
+
+#[test]
+fn query_process_qos() -> Result<(), windows_result::Error> {
+    let pid = std::process::id();
+
+    toggle_efficiency_mode(pid, Some(true))?;
+    assert_eq!(process::query_qos(pid)?.qos(), QosState::Eco);
+    toggle_efficiency_mode(pid, Some(false))?;
+    assert_eq!(process::query_qos(pid)?.qos(), QosState::High);
+    toggle_efficiency_mode(pid, None)?;
+    assert_eq!(process::query_qos(pid)?.qos(), QosState::Default);
+
+    Ok(())
+}
+
+#[test]
+fn query_thread_qos() -> Result<(), windows_result::Error> {
+    let (tx, rx) = channel();
+    let _ = std::thread::spawn(move || {
+        let _ = tx.send(unsafe { GetCurrentThreadId() });
+        loop {
+            sleep(Duration::from_secs(5));
+        }
+    });
+    let thread_id = rx.recv().expect("failed to retrieve thread id");
+
+    assert_eq!(thread::query_qos(thread_id)?.qos(), QosState::Default);
+    thread::toggle_efficiency_mode(thread_id, Some(false))?;
+    assert_eq!(thread::query_qos(thread_id)?.qos(), QosState::High);
+    thread::toggle_efficiency_mode(thread_id, Some(true))?;
+    assert_eq!(thread::query_qos(thread_id)?.qos(), QosState::Eco);
+
+    Ok(())
+}
+
+#[test]
+fn restore_thread_qos() -> Result<(), windows_result::Error> {
+    let (tx, rx) = channel();
+    let _ = std::thread::spawn(move || {
+        unsafe { SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_ABOVE_NORMAL) }
+            .expect("failed to set thread priority");
+        let _ = tx.send(unsafe { GetCurrentThreadId() });
+        loop {
+            sleep(Duration::from_secs(5));
+        }
+    });
+    let thread_id = rx.recv().expect("failed to retrieve thread id");
+
+    thread::toggle_efficiency_mode(thread_id, Some(false))?;
+    let state = thread::query_qos(thread_id)?;
+    let priority = thread::query_priority(thread_id)?;
+    assert_eq!(priority, THREAD_PRIORITY_ABOVE_NORMAL);
+
+    thread::toggle_efficiency_mode(thread_id, Some(true))?;
+    assert_eq!(thread::query_priority(thread_id)?, THREAD_PRIORITY_IDLE);
+
+    thread::set_qos(thread_id, state, priority)?;
+    assert_eq!(thread::query_qos(thread_id)?.qos(), QosState::High);
+    assert_eq!(thread::query_priority(thread_id)?, priority);
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+#![cfg(windows)]
+
 use win32_ecoqos::thread::{ecoqos_enabled, toggle_efficiency_mode_handle};
 use windows::Win32::System::Threading::GetCurrentThread;
 